@@ -0,0 +1,308 @@
+use std::sync::OnceLock;
+
+use crate::{
+    board::{BitBoard, Direction},
+    piece::Color,
+    square::Square,
+};
+
+const ROOK_OFFSETS: [(i8, i8); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const BISHOP_OFFSETS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const KING_OFFSETS: [(i8, i8); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+
+// Magic numbers found by trial and error, each maps every blocker arrangement
+// on a square's rays to a unique slot in the attack table
+#[rustfmt::skip]
+const ROOK_MAGICS: [u64; 64] = [
+    0x0080008040002018, 0x0040100040002001, 0x09000D0010200040, 0x8080080010008004,
+    0xA280080002340080, 0x2500050024000208, 0x0280010000800200, 0x1100144380220100,
+    0xA92080048C204002, 0x0802804003200080, 0x0108802000100089, 0x8060800800801002,
+    0xA002000822000410, 0x950A001002000824, 0x1202000408010200, 0x02950008408A0100,
+    0x40A0A18000814000, 0x0080848020004011, 0x0800828010002000, 0x0500420010200A00,
+    0x0008818004000802, 0x0000808004000200, 0x0000040001020810, 0x0081120000442081,
+    0x0522400180002090, 0x2800200040401000, 0x402004A180100481, 0x0880420200200810,
+    0x000A040080800800, 0x080A000404001020, 0x0000010400820810, 0x4000D08200004C09,
+    0x0100804000800020, 0xCC01028026004200, 0x0081002001004010, 0x0000800800801000,
+    0x0009800401802800, 0x0002000902000410, 0x0000180144001022, 0x00008100C6002884,
+    0x1200800040008024, 0x2000200050044000, 0x0090882200420010, 0x6A1810200A020040,
+    0x0480040008008080, 0x0206001108160014, 0xC010040200010100, 0x000001018046000C,
+    0x00B0400880042080, 0x0040002010080220, 0x0020004228110100, 0x0150000804004140,
+    0x0028008004000980, 0x0801000804000300, 0x2C80210208900400, 0x0000040130408200,
+    0x4001409100258001, 0x0005004002883021, 0x019041E003001019, 0x8000200900041001,
+    0x4021001002040801, 0x0011000400080201, 0x1600010210408804, 0x2010810084003042,
+];
+
+#[rustfmt::skip]
+const BISHOP_MAGICS: [u64; 64] = [
+    0x0032482800818200, 0x0819022820450000, 0x4242108204880008, 0x8044404080410224,
+    0x1407104000080100, 0x1B01100210004000, 0x0B0C0888841010C1, 0x08A0108201104020,
+    0x00003888654C0410, 0x0010220202520A00, 0x4003048400820001, 0x1007022082000002,
+    0x3C22040420040A18, 0x3440508820080030, 0x1204208090082100, 0x0000930501012000,
+    0x0010842002D00100, 0x0202000404484208, 0x0008000100410602, 0x0088000404200800,
+    0xA004100202020232, 0x0004200100A01002, 0x0201000208020200, 0x2022010022020200,
+    0x80100410100410B0, 0x3010704848011100, 0x2000380010048320, 0x8068080000202120,
+    0x0001010080104000, 0x4808004120806000, 0x5000821000823008, 0x0004010000504224,
+    0x0088044010040808, 0x0812022000032814, 0xA000280800110202, 0x2882200802010105,
+    0x0508082400024100, 0x8001010200030800, 0x4001260E00019820, 0x0C02040308507180,
+    0x0880884441009000, 0x20A84202A0011000, 0x00010410820C1000, 0x4000002018000108,
+    0x0080091124004A00, 0x0409010302010700, 0x0088128802040040, 0x1801010408801100,
+    0x0184010190110800, 0x0000404208210000, 0x0000120908880000, 0x9006180020884020,
+    0x900000400488410A, 0x0410218401020100, 0x0004D00428008100, 0x0002021404008400,
+    0x0911410150222026, 0x0300202088041040, 0x0100A06124122800, 0x001C042000420200,
+    0x0004018208030400, 0x0044040405080200, 0x040820200402B680, 0x0204200411020410,
+];
+
+pub const KNIGHT_ATTACKS: [u64; 64] = leaper_table(&KNIGHT_OFFSETS);
+pub const KING_ATTACKS: [u64; 64] = leaper_table(&KING_OFFSETS);
+// Indexed by side then square
+pub const PAWN_ATTACKS: [[u64; 64]; 2] = [
+    leaper_table(&[(1, 1), (-1, 1)]),
+    leaper_table(&[(1, -1), (-1, -1)]),
+];
+
+// Attacks of a piece that jumps by fixed offsets, from every square
+const fn leaper_table(offsets: &[(i8, i8)]) -> [u64; 64] {
+    let mut table = [0; 64];
+    let mut sqr = 0;
+
+    while sqr < 64 {
+        let mut i = 0;
+        while i < offsets.len() {
+            let x = (sqr % 8) as i8 + offsets[i].0;
+            let y = (sqr / 8) as i8 + offsets[i].1;
+
+            if x >= 0 && x < 8 && y >= 0 && y < 8 {
+                table[sqr] |= 1 << (y * 8 + x);
+            }
+            i += 1;
+        }
+        sqr += 1;
+    }
+
+    table
+}
+
+#[derive(Clone, Copy, Default)]
+struct Magic {
+    // Squares whose occupancy changes the attacks, board edges are left out
+    mask: u64,
+    magic: u64,
+    shift: u32,
+    // Where this square's attacks start in the shared table
+    offset: usize,
+}
+
+impl Magic {
+    fn index(&self, occupied: u64) -> usize {
+        self.offset + ((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+struct SlidingTables {
+    rook: [Magic; 64],
+    bishop: [Magic; 64],
+    attacks: Vec<u64>,
+    // Squares strictly between two squares on a shared rank, file or diagonal
+    between: Vec<[u64; 64]>,
+    // The whole rank, file or diagonal through two squares, edge to edge
+    line: Vec<[u64; 64]>,
+}
+
+static TABLES: OnceLock<SlidingTables> = OnceLock::new();
+
+// Walk each ray until it leaves the board or hits a piece
+fn ray_attacks(sqr: usize, occupied: u64, offsets: &[(i8, i8)]) -> u64 {
+    let mut bb = 0;
+
+    for (dx, dy) in offsets {
+        let (mut x, mut y) = ((sqr % 8) as i8, (sqr / 8) as i8);
+
+        loop {
+            x += dx;
+            y += dy;
+            if !(0..8).contains(&x) || !(0..8).contains(&y) {
+                break;
+            }
+
+            bb |= 1 << (y * 8 + x);
+            if occupied & (1 << (y * 8 + x)) != 0 {
+                break;
+            }
+        }
+    }
+
+    bb
+}
+
+// The ray squares that can block, pieces on the last square of a ray never matter
+fn blocker_mask(sqr: usize, offsets: &[(i8, i8)]) -> u64 {
+    let mut bb = 0;
+
+    for (dx, dy) in offsets {
+        let (mut x, mut y) = ((sqr % 8) as i8 + dx, (sqr / 8) as i8 + dy);
+
+        while (0..8).contains(&(x + dx)) && (0..8).contains(&(y + dy)) {
+            bb |= 1 << (y * 8 + x);
+            x += dx;
+            y += dy;
+        }
+    }
+
+    bb
+}
+
+fn init_magics(magics: &[u64; 64], offsets: &[(i8, i8)], attacks: &mut Vec<u64>) -> [Magic; 64] {
+    let mut table = [Magic::default(); 64];
+
+    for (sqr, entry) in table.iter_mut().enumerate() {
+        let mask = blocker_mask(sqr, offsets);
+        let bits = mask.count_ones();
+        *entry = Magic {
+            mask,
+            magic: magics[sqr],
+            shift: 64 - bits,
+            offset: attacks.len(),
+        };
+        attacks.resize(attacks.len() + (1 << bits), 0);
+
+        // Carry-Rippler trick to visit every subset of the mask
+        let mut subset: u64 = 0;
+        loop {
+            attacks[entry.index(subset)] = ray_attacks(sqr, subset, offsets);
+            subset = subset.wrapping_sub(mask) & mask;
+            if subset == 0 {
+                break;
+            }
+        }
+    }
+
+    table
+}
+
+fn tables() -> &'static SlidingTables {
+    TABLES.get_or_init(|| {
+        let mut attacks = Vec::new();
+        let rook = init_magics(&ROOK_MAGICS, &ROOK_OFFSETS, &mut attacks);
+        let bishop = init_magics(&BISHOP_MAGICS, &BISHOP_OFFSETS, &mut attacks);
+        let (between, line) = init_lines();
+
+        SlidingTables {
+            rook,
+            bishop,
+            attacks,
+            between,
+            line,
+        }
+    })
+}
+
+fn init_lines() -> (Vec<[u64; 64]>, Vec<[u64; 64]>) {
+    let mut between = vec![[0; 64]; 64];
+    let mut line = vec![[0; 64]; 64];
+
+    for a in 0..64 {
+        for b in 0..64 {
+            for offsets in [&ROOK_OFFSETS, &BISHOP_OFFSETS] {
+                let (bit_a, bit_b) = (1 << a, 1 << b);
+                if a == b || ray_attacks(a, 0, offsets) & bit_b == 0 {
+                    continue;
+                }
+
+                // Rays from both ends only overlap along the line joining them
+                between[a][b] = ray_attacks(a, bit_b, offsets) & ray_attacks(b, bit_a, offsets);
+                line[a][b] =
+                    (ray_attacks(a, 0, offsets) & ray_attacks(b, 0, offsets)) | bit_a | bit_b;
+            }
+        }
+    }
+
+    (between, line)
+}
+
+pub fn rook_attacks(sqr: Square, occupied: BitBoard) -> BitBoard {
+    let tables = tables();
+    BitBoard(tables.attacks[tables.rook[sqr.index()].index(occupied.0)])
+}
+
+pub fn bishop_attacks(sqr: Square, occupied: BitBoard) -> BitBoard {
+    let tables = tables();
+    BitBoard(tables.attacks[tables.bishop[sqr.index()].index(occupied.0)])
+}
+
+pub fn queen_attacks(sqr: Square, occupied: BitBoard) -> BitBoard {
+    rook_attacks(sqr, occupied) | bishop_attacks(sqr, occupied)
+}
+
+pub fn knight_attacks(sqr: Square) -> BitBoard {
+    BitBoard(KNIGHT_ATTACKS[sqr.index()])
+}
+
+pub fn king_attacks(sqr: Square) -> BitBoard {
+    BitBoard(KING_ATTACKS[sqr.index()])
+}
+
+pub fn pawn_attacks(sqr: Square, side: Color) -> BitBoard {
+    BitBoard(PAWN_ATTACKS[side as usize][sqr.index()])
+}
+
+/// Squares attacked by a set of pawns, the captures towards the east and west separately
+pub fn pawn_captures(pawns: BitBoard, side: Color) -> (BitBoard, BitBoard) {
+    let forward = Direction::forward(side);
+    (pawns.east().step(forward), pawns.west().step(forward))
+}
+
+/// Every square attacked by a set of pawns
+pub fn pawn_attacks_set(pawns: BitBoard, side: Color) -> BitBoard {
+    let (east, west) = pawn_captures(pawns, side);
+    east | west
+}
+
+/// Squares a set of pawns can push one step onto
+pub fn pawn_pushes(pawns: BitBoard, empty: BitBoard, side: Color) -> BitBoard {
+    pawns.step(Direction::forward(side)) & empty
+}
+
+/// Squares a set of pawns can reach with a double push from their starting rank
+pub fn pawn_double_pushes(pawns: BitBoard, empty: BitBoard, side: Color) -> BitBoard {
+    // Pawns that made a single push from the start are on the third rank
+    let third_rank = match side {
+        Color::White => BitBoard(0xFF << 16),
+        Color::Black => BitBoard(0xFF << 40),
+    };
+    pawn_pushes(pawn_pushes(pawns, empty, side) & third_rank, empty, side)
+}
+
+/// Squares strictly between two squares, empty if they don't share a line
+pub fn between(a: Square, b: Square) -> BitBoard {
+    BitBoard(tables().between[a.index()][b.index()])
+}
+
+/// Full line through two squares, empty if they don't share one
+pub fn line(a: Square, b: Square) -> BitBoard {
+    BitBoard(tables().line[a.index()][b.index()])
+}
+
+/// Slow ray walking version of the sliding attacks, used to check the tables
+pub fn sliding_attacks_slow(sqr: Square, occupied: BitBoard, rook: bool) -> BitBoard {
+    let offsets = if rook { &ROOK_OFFSETS } else { &BISHOP_OFFSETS };
+    BitBoard(ray_attacks(sqr.index(), occupied.0, offsets))
+}
@@ -0,0 +1,133 @@
+//! Build Polyglot opening books from PGN files and look positions up in them
+//!
+//! Usage: book build <out.bin> <games.pgn>... [--ply <n>] [--min-games <n>]
+//!        book probe <book.bin> [<fen>]
+
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+
+use chess_engine::{
+    book::{Book, BookBuilder},
+    fen::START_FEN,
+    game::Game,
+    pgn,
+};
+
+const USAGE: &str = "\
+Usage: book build <out.bin> <games.pgn>... [--ply <n>] [--min-games <n>]
+       book probe <book.bin> [<fen>]";
+
+// Only the opening is worth keeping
+const DEFAULT_PLY: usize = 24;
+const DEFAULT_MIN_GAMES: u32 = 2;
+
+fn build(args: &[String]) -> Result<(), String> {
+    let mut files = Vec::new();
+    let mut ply = DEFAULT_PLY;
+    let mut min_games = DEFAULT_MIN_GAMES;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ply" => {
+                ply = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or("--ply needs a number")?
+            }
+            "--min-games" => {
+                min_games = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or("--min-games needs a number")?
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    let Some((out, inputs)) = files.split_first() else {
+        return Err(USAGE.into());
+    };
+    if inputs.is_empty() {
+        return Err(USAGE.into());
+    }
+
+    let mut builder = BookBuilder::new(ply);
+    let (mut read, mut skipped) = (0, 0);
+
+    for path in inputs {
+        let text = fs::read(path).map_err(|err| format!("Couldn't read {path}: {err}"))?;
+        // Old collections are often Latin-1, only the tags would be affected
+        let text = String::from_utf8_lossy(&text);
+
+        for game in pgn::read_games(&text) {
+            match game {
+                Ok(game) => {
+                    builder.add_game(&game);
+                    read += 1;
+                }
+                Err(err) => {
+                    eprintln!("Skipping a game in {path}: {err}");
+                    skipped += 1;
+                }
+            }
+        }
+    }
+
+    let book = builder.finish(min_games);
+    book.save(out)
+        .map_err(|err| format!("Couldn't write {out}: {err}"))?;
+    println!(
+        "Read {read} games, skipped {skipped}, wrote {} entries to {out}",
+        book.len()
+    );
+    Ok(())
+}
+
+fn probe(args: &[String]) -> Result<(), String> {
+    let Some((path, fen)) = args.split_first() else {
+        return Err(USAGE.into());
+    };
+    let book = Book::open(path).map_err(|err| err.to_string())?;
+
+    let fen = if fen.is_empty() {
+        START_FEN.to_string()
+    } else {
+        fen.join(" ")
+    };
+    let mut game = Game::from_fen(&fen).map_err(|err| err.to_string())?;
+
+    let moves = book.moves(&mut game);
+    if moves.is_empty() {
+        println!("Position is not in the book");
+    }
+
+    let total: u32 = moves.iter().map(|&(_, weight)| weight as u32).sum();
+    for (mv, weight) in moves {
+        println!(
+            "{:<8} {weight:>6} {:>5.1}%",
+            game.to_san(mv),
+            weight as f64 * 100.0 / total as f64
+        );
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let result = match args.split_first() {
+        Some((command, rest)) if command == "build" => build(rest),
+        Some((command, rest)) if command == "probe" => probe(rest),
+        _ => Err(USAGE.into()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,366 @@
+//! Play in the terminal, against a friend on the same keyboard or against the engine
+//!
+//! Usage: play [--engine white|black] [--movetime <ms>] [--fen <fen>] [--book <file>]
+//!             [--tablebase <dir>] [--variant <name>] [--ascii]
+
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chess_engine::{
+    book::Book,
+    fen::START_FEN,
+    game::{Game, Move},
+    pgn::Pgn,
+    piece::Color,
+    search::{mate_in, Limits, Search},
+    tablebase::Tablebase,
+    variant::{self, Variant},
+};
+
+const USAGE: &str = "\
+Usage: play [--engine white|black] [--movetime <ms>] [--fen <fen>] [--book <file>]
+            [--tablebase <dir>] [--variant <name>] [--ascii]";
+const DEFAULT_MOVETIME_MS: u64 = 1000;
+
+const HELP: &str = "\
+Moves can be SAN (Nf3, exd5, O-O, e8=Q) or coordinates (g1f3, e7e8q), drops are N@f3
+  undo             take back your last move
+  new              start again from the initial position
+  fen [<fen>]      show the position as FEN, or load one
+  save <file>      write the game so far as PGN
+  engine <side>    have the engine play white, black or off for hot-seat
+  go               let the engine move now
+  moves            list the legal moves
+  flip             swap the colours the engine and you play
+  help             show this
+  quit";
+
+struct Session {
+    game: Game,
+    // FEN the moves were played from, needed to write the PGN
+    start_fen: String,
+    moves: Vec<Move>,
+    search: Search,
+    // Also given to the search, kept to say when a move came from it
+    tablebase: Option<Arc<Tablebase>>,
+    engine: Option<Color>,
+    movetime: Duration,
+    ascii: bool,
+    variant: &'static dyn Variant,
+}
+
+impl Session {
+    fn load(&mut self, fen: &str) -> Result<(), String> {
+        let mut game = Game::from_fen(fen).map_err(|e| e.to_string())?;
+        game.set_variant(self.variant);
+        game.validate().map_err(|e| e.to_string())?;
+        self.game = game;
+        self.start_fen = fen.to_string();
+        self.moves.clear();
+        self.search.new_game();
+        Ok(())
+    }
+
+    fn play(&mut self, mv: Move) {
+        self.game.make_move(mv);
+        self.moves.push(mv);
+    }
+
+    fn undo(&mut self) -> bool {
+        let Some(mv) = self.moves.pop() else {
+            return false;
+        };
+        self.game.unmake_move(mv);
+        true
+    }
+
+    fn show(&self) {
+        if self.ascii {
+            println!("\n{:#}\n", self.game);
+        } else {
+            println!("\n{}\n", self.game);
+        }
+    }
+
+    fn engine_move(&mut self) {
+        if outcome(&mut self.game).is_some() {
+            return;
+        }
+
+        let limits = Limits::movetime(self.movetime);
+        let result = self.search.search(&mut self.game, limits);
+        let Some(mv) = result.best_move else {
+            return;
+        };
+
+        let san = self.game.to_san(mv);
+        // Book and tablebase moves come back without searching
+        let known = self
+            .tablebase
+            .as_ref()
+            .and_then(|tablebase| tablebase.outcome(&self.game));
+        if let (0, Some(outcome)) = (result.depth, known) {
+            println!("Engine plays {san} from the tablebase, {outcome}");
+            self.play(mv);
+            return;
+        }
+        if result.depth == 0 {
+            println!("Engine plays {san} from the book");
+            self.play(mv);
+            return;
+        }
+
+        let score = match mate_in(result.score) {
+            Some(moves) => format!("mate in {moves}"),
+            None => format!("{:+.2}", result.score as f64 / 100.0),
+        };
+        println!(
+            "Engine plays {san} ({score}, depth {}, {} nodes)",
+            result.depth, result.nodes
+        );
+        self.play(mv);
+    }
+
+    fn save(&self, path: &str) -> io::Result<()> {
+        let mut pgn = Pgn::new(&self.start_fen);
+        pgn.set_tag("Event", "Terminal game");
+        if !self.game.is_standard() {
+            pgn.set_tag("Variant", self.variant.name());
+        }
+        for side in Color::ALL {
+            let name = if self.engine == Some(side) {
+                "chess_engine"
+            } else {
+                "Player"
+            };
+            let tag = match side {
+                Color::White => "White",
+                Color::Black => "Black",
+            };
+            pgn.set_tag(tag, name);
+        }
+        pgn.moves = self.moves.clone();
+
+        if let Some((result, _)) = outcome(&mut self.game.clone()) {
+            pgn.result = result.to_string();
+        }
+
+        fs::write(path, pgn.to_string())
+    }
+
+    /// Returns false once the player wants to quit
+    fn command(&mut self, line: &str) -> bool {
+        let (word, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        match word {
+            "" => {}
+            "quit" | "exit" => return false,
+            "help" => println!("{HELP}"),
+            "new" => {
+                self.load(START_FEN).unwrap();
+                self.show();
+            }
+            "fen" if rest.is_empty() => println!("{}", self.game.to_fen()),
+            "fen" => match self.load(rest) {
+                Ok(()) => self.show(),
+                Err(err) => println!("{err}"),
+            },
+            "save" if rest.is_empty() => println!("Usage: save <file>"),
+            "save" => match self.save(rest) {
+                Ok(()) => println!("Saved to {rest}"),
+                Err(err) => println!("Couldn't save: {err}"),
+            },
+            "undo" => {
+                // Against the engine take back its reply as well so it is our turn again
+                let mut undone = self.undo();
+                if undone && self.engine == Some(self.game.turn()) {
+                    undone = self.undo();
+                }
+                if undone {
+                    self.show();
+                } else {
+                    println!("Nothing to undo");
+                }
+            }
+            "engine" => {
+                self.engine = match rest {
+                    "white" => Some(Color::White),
+                    "black" => Some(Color::Black),
+                    "off" => None,
+                    _ => {
+                        println!("Usage: engine white|black|off");
+                        return true;
+                    }
+                };
+            }
+            "flip" => self.engine = self.engine.map(|side| !side),
+            "go" => {
+                self.engine_move();
+                self.show();
+            }
+            "moves" => {
+                let moves = self.game.generate_moves();
+                let san: Vec<String> = moves.iter().map(|&mv| self.game.to_san(mv)).collect();
+                println!("{}", san.join(" "));
+            }
+            _ => match self.game.parse_move(line) {
+                Some(mv) if outcome(&mut self.game).is_none() => {
+                    self.play(mv);
+                    self.show();
+                }
+                Some(_) => println!("The game is over, try undo or new"),
+                None => println!("Not a legal move: {line}, try help"),
+            },
+        }
+
+        true
+    }
+
+    fn prompt(&mut self) {
+        if let Some((result, reason)) = outcome(&mut self.game) {
+            println!("Game over, {result} by {reason}");
+        } else if !self.game.checkers().is_empty() {
+            println!("{} is in check", self.game.turn());
+        }
+
+        print!("{} to move> ", self.game.turn());
+        let _ = io::stdout().flush();
+    }
+}
+
+// Result of the game if it is over, 1-0 0-1 or 1/2-1/2 with the reason
+fn outcome(game: &mut Game) -> Option<(&'static str, &'static str)> {
+    if let Some(winner) = game.winner() {
+        return Some(match winner {
+            Color::White => ("1-0", "white wins"),
+            Color::Black => ("0-1", "black wins"),
+        });
+    }
+
+    if game.generate_moves().is_empty() {
+        if game.checkers().is_empty() {
+            return Some(("1/2-1/2", "stalemate"));
+        }
+        return Some(match game.turn() {
+            Color::White => ("0-1", "black mates"),
+            Color::Black => ("1-0", "white mates"),
+        });
+    }
+
+    if game.state().halfmove_clock >= 100 {
+        return Some(("1/2-1/2", "fifty move rule"));
+    }
+    if game.is_threefold() {
+        return Some(("1/2-1/2", "threefold repetition"));
+    }
+
+    None
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut fen = START_FEN.to_string();
+    let mut engine = None;
+    let mut movetime = DEFAULT_MOVETIME_MS;
+    let mut ascii = false;
+    let mut book = None;
+    let mut tablebase = None;
+    let mut variant: &'static dyn Variant = &variant::STANDARD;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fen" => fen = args.next().unwrap_or_default(),
+            "--engine" => {
+                engine = match args.next().as_deref() {
+                    Some("white") => Some(Color::White),
+                    Some("black") => Some(Color::Black),
+                    _ => None,
+                }
+            }
+            "--movetime" => {
+                movetime = args
+                    .next()
+                    .and_then(|ms| ms.parse().ok())
+                    .unwrap_or(DEFAULT_MOVETIME_MS)
+            }
+            "--book" => {
+                let path = args.next().unwrap_or_default();
+                match Book::open(&path) {
+                    Ok(loaded) => book = Some(Arc::new(loaded)),
+                    Err(err) => {
+                        eprintln!("{err}");
+                        return;
+                    }
+                }
+            }
+            "--tablebase" => {
+                let dir = args.next().unwrap_or_default();
+                match Tablebase::load(&dir) {
+                    Ok(loaded) => tablebase = Some(Arc::new(loaded)),
+                    Err(err) => {
+                        eprintln!("{err}");
+                        return;
+                    }
+                }
+            }
+            "--variant" => {
+                let name = args.next().unwrap_or_default();
+                match variant::by_name(&name) {
+                    Some(found) => variant = found,
+                    None => {
+                        eprintln!("Unknown variant {name}");
+                        return;
+                    }
+                }
+            }
+            "--ascii" => ascii = true,
+            _ => {
+                eprintln!("Unknown argument {arg}");
+                eprintln!("{USAGE}");
+                return;
+            }
+        }
+    }
+
+    let mut session = Session {
+        game: Game::new(),
+        start_fen: String::new(),
+        moves: Vec::new(),
+        search: Search::new(),
+        tablebase: tablebase.clone(),
+        engine,
+        movetime: Duration::from_millis(movetime),
+        ascii,
+        variant,
+    };
+    session.search.set_book(book);
+    session.search.set_tablebase(tablebase);
+    if let Err(err) = session.load(&fen) {
+        eprintln!("{err}");
+        return;
+    }
+
+    println!("Type help for commands");
+    session.show();
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        if session.engine == Some(session.game.turn()) && outcome(&mut session.game).is_none() {
+            session.engine_move();
+            session.show();
+        }
+
+        session.prompt();
+        let Some(Ok(line)) = lines.next() else {
+            break;
+        };
+        if !session.command(line.trim()) {
+            break;
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Generate endgame tablebases and write them to a directory
+//!
+//! Usage: tbgen <dir> <material>...   e.g. tbgen tables KQK KRK KPK KBNK
+//!        tbgen <dir> --all <3|4>
+
+use std::env;
+use std::process::ExitCode;
+use std::time::Instant;
+
+use chess_engine::tablebase::{Tablebase, MAX_PIECES};
+
+const USAGE: &str = "\
+Usage: tbgen <dir> <material>...
+       tbgen <dir> --all <3|4>";
+
+// Every material with the given number of pieces, the stronger side as white
+fn all_materials(pieces: usize) -> Vec<String> {
+    const KINDS: [char; 5] = ['Q', 'R', 'B', 'N', 'P'];
+    let mut names = Vec::new();
+
+    match pieces {
+        3 => {
+            for a in KINDS {
+                names.push(format!("K{a}K"));
+            }
+        }
+        4 => {
+            for (i, a) in KINDS.into_iter().enumerate() {
+                // Two pieces for white
+                for b in KINDS.into_iter().skip(i) {
+                    names.push(format!("K{a}{b}K"));
+                }
+                // One each
+                for b in KINDS.into_iter().skip(i) {
+                    names.push(format!("K{a}K{b}"));
+                }
+            }
+        }
+        _ => {}
+    }
+    names
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let Some((dir, rest)) = args.split_first() else {
+        return Err(USAGE.into());
+    };
+
+    let names = match rest {
+        [flag, n] if flag == "--all" => {
+            let pieces: usize = n.parse().map_err(|_| "--all needs 3 or 4")?;
+            if !(3..=MAX_PIECES).contains(&pieces) {
+                return Err(format!("--all needs 3 to {MAX_PIECES} pieces"));
+            }
+            (3..=pieces).flat_map(all_materials).collect()
+        }
+        [] => return Err(USAGE.into()),
+        names => names.to_vec(),
+    };
+
+    let mut tablebase = Tablebase::new();
+    for name in &names {
+        let start = Instant::now();
+        tablebase
+            .generate(name)
+            .map_err(|err| format!("{name}: {err}"))?;
+        println!("{name} done in {:.1}s", start.elapsed().as_secs_f64());
+    }
+
+    tablebase
+        .save(dir)
+        .map_err(|err| format!("Couldn't write to {dir}: {err}"))?;
+    println!("Wrote {} to {dir}", tablebase.materials().join(" "));
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
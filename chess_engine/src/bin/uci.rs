@@ -0,0 +1,318 @@
+//! Universal Chess Interface front end, reads commands on stdin and answers on stdout
+
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use chess_engine::{
+    book::Book,
+    fen::START_FEN,
+    game::Game,
+    piece::Color,
+    search::{mate_in, Limits, Search, SearchResult},
+    tablebase::Tablebase,
+    tt::DEFAULT_SIZE_MB,
+};
+
+const MAX_HASH_MB: usize = 4096;
+const MAX_THREADS: usize = 256;
+const MAX_MULTIPV: usize = 64;
+
+struct Uci {
+    game: Game,
+    // None while a search has it on another thread
+    search: Option<Search>,
+    searching: Option<JoinHandle<Search>>,
+    stop: Arc<AtomicBool>,
+    // Set once the GUI has stopped the search. The search clears its own flag when it
+    // returns, so an infinite search waits on this one before answering
+    stopped: Arc<AtomicBool>,
+    // Set by the GUI, castling is then sent and expected as the king taking its rook
+    chess960: bool,
+}
+
+impl Uci {
+    fn new() -> Self {
+        let search = Search::new();
+        Self {
+            game: Game::from_fen(START_FEN).unwrap(),
+            stop: search.stop_flag(),
+            stopped: Arc::new(AtomicBool::new(false)),
+            search: Some(search),
+            searching: None,
+            chess960: false,
+        }
+    }
+
+    // Stop any running search and take the search back. Anything that changes the search
+    // or the position goes through here, waiting on an infinite search would never return
+    fn stop(&mut self) -> &mut Search {
+        self.stop.store(true, Ordering::Relaxed);
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.searching.take() {
+            // An infinite search that finished early is parked until it is stopped
+            handle.thread().unpark();
+            self.search = Some(handle.join().expect("Search thread panicked"));
+        }
+        self.search.as_mut().unwrap()
+    }
+
+    /// Returns false once the GUI wants us to quit
+    fn command(&mut self, line: &str) -> bool {
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("uci") => {
+                println!("id name chess_engine");
+                println!("id author tymeklew");
+                println!(
+                    "option name Hash type spin default {DEFAULT_SIZE_MB} min 1 max {MAX_HASH_MB}"
+                );
+                println!("option name Threads type spin default 1 min 1 max {MAX_THREADS}");
+                println!("option name MultiPV type spin default 1 min 1 max {MAX_MULTIPV}");
+                println!("option name BookFile type string default <empty>");
+                println!("option name TablebasePath type string default <empty>");
+                println!("option name UCI_Chess960 type check default false");
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("ucinewgame") => {
+                self.stop().new_game();
+            }
+            Some("setoption") => {
+                let rest: Vec<&str> = tokens.collect();
+                self.set_option(&rest);
+            }
+            Some("position") => {
+                let rest: Vec<&str> = tokens.collect();
+                self.stop();
+                if let Err(err) = self.position(&rest) {
+                    println!("info string {err}");
+                }
+            }
+            Some("go") => {
+                let rest: Vec<&str> = tokens.collect();
+                self.go(&rest);
+            }
+            Some("stop") => {
+                self.stop();
+            }
+            Some("quit") => {
+                self.stop();
+                return false;
+            }
+            _ => {}
+        }
+
+        true
+    }
+
+    // setoption name <name> value <value>
+    fn set_option(&mut self, tokens: &[&str]) {
+        let value_at = tokens.iter().position(|&t| t == "value");
+        let end = value_at.unwrap_or(tokens.len());
+        let name = tokens.get(1..end).map(|t| t.join(" ")).unwrap_or_default();
+        let value = value_at
+            .and_then(|i| tokens.get(i + 1..))
+            .map(|t| t.join(" "))
+            .unwrap_or_default();
+
+        let search = self.stop();
+        let number = value.parse::<usize>();
+        match (name.to_ascii_lowercase().as_str(), number) {
+            ("hash", Ok(n)) => search.set_hash_size(n.clamp(1, MAX_HASH_MB)),
+            ("threads", Ok(n)) => search.set_threads(n.clamp(1, MAX_THREADS)),
+            ("multipv", Ok(n)) => search.set_multipv(n.clamp(1, MAX_MULTIPV)),
+            ("hash" | "threads" | "multipv", Err(_)) => {
+                println!("info string Missing value for option {name}")
+            }
+            ("bookfile", _) if value.is_empty() || value == "<empty>" => search.set_book(None),
+            ("bookfile", _) => match Book::open(&value) {
+                Ok(book) => search.set_book(Some(Arc::new(book))),
+                Err(err) => println!("info string {err}"),
+            },
+            ("uci_chess960", _) => self.chess960 = value == "true",
+            ("tablebasepath", _) if value.is_empty() || value == "<empty>" => {
+                search.set_tablebase(None)
+            }
+            ("tablebasepath", _) => match Tablebase::load(&value) {
+                Ok(tablebase) => search.set_tablebase(Some(Arc::new(tablebase))),
+                Err(err) => println!("info string {err}"),
+            },
+            _ => println!("info string Unknown option {name}"),
+        }
+    }
+
+    // position startpos|fen <fen> [moves <move>...]
+    fn position(&mut self, tokens: &[&str]) -> Result<(), String> {
+        let moves_at = tokens.iter().position(|&t| t == "moves");
+        let setup = &tokens[..moves_at.unwrap_or(tokens.len())];
+
+        let mut game = match setup.first() {
+            Some(&"startpos") => Game::from_fen(START_FEN).unwrap(),
+            Some(&"fen") => Game::from_fen(&setup[1..].join(" ")).map_err(|e| e.to_string())?,
+            _ => return Err("Expected startpos or fen".into()),
+        };
+        // Outside Chess960 castling has to be from the usual squares, whatever the FEN says
+        game.set_chess960(self.chess960);
+        game.validate().map_err(|e| e.to_string())?;
+
+        for text in tokens.iter().skip(moves_at.map_or(tokens.len(), |i| i + 1)) {
+            let mv = game
+                .find_move(text)
+                .ok_or_else(|| format!("Illegal move {text}"))?;
+            game.make_move(mv);
+        }
+
+        self.game = game;
+        Ok(())
+    }
+
+    // go [wtime <ms>] [btime <ms>] [winc <ms>] [binc <ms>] [movestogo <n>]
+    //    [movetime <ms>] [depth <n>] [infinite]
+    fn go(&mut self, tokens: &[&str]) {
+        let value = |name: &str| -> Option<u64> {
+            let index = tokens.iter().position(|&t| t == name)?;
+            tokens.get(index + 1)?.parse().ok()
+        };
+        let ms = |name: &str| value(name).map(Duration::from_millis);
+
+        let (time, increment) = match self.game.turn() {
+            Color::White => (ms("wtime"), ms("winc")),
+            Color::Black => (ms("btime"), ms("binc")),
+        };
+
+        let mut limits = match (ms("movetime"), time) {
+            (Some(movetime), _) => Limits::movetime(movetime),
+            (None, Some(time)) => Limits::clock(
+                time,
+                increment.unwrap_or_default(),
+                value("movestogo").map(|n| n as u32),
+            ),
+            // Infinite, or nothing given, searches until told to stop
+            (None, None) => Limits::default(),
+        };
+        limits.depth = value("depth").map(|d| d.min(u8::MAX as u64) as u8);
+        // The GUI expects no bestmove until it sends stop, even if the search ends first
+        let infinite = tokens.contains(&"infinite");
+
+        self.stop();
+        let mut search = self.search.take().unwrap();
+        let mut game = self.game.clone();
+        // Only used to write the moves out, the search has the other copy
+        let notation = self.game.clone();
+        self.stop.store(false, Ordering::Relaxed);
+        let stopped = self.stopped.clone();
+        stopped.store(false, Ordering::Relaxed);
+
+        self.searching = Some(thread::spawn(move || {
+            let start = Instant::now();
+            let result = search.search_with_info(&mut game, limits, |result| {
+                print_info(result, start.elapsed(), &notation);
+            });
+
+            while infinite && !stopped.load(Ordering::Relaxed) {
+                thread::park();
+            }
+            match result.best_move {
+                Some(mv) => println!("bestmove {}", notation.coordinates(mv)),
+                None => println!("bestmove 0000"),
+            }
+            search
+        }));
+    }
+}
+
+fn print_info(result: &SearchResult, elapsed: Duration, game: &Game) {
+    let ms = elapsed.as_millis().max(1);
+    let nps = result.nodes as u128 * 1000 / ms;
+
+    for (index, line) in result.lines.iter().enumerate() {
+        let score = match mate_in(line.score) {
+            Some(moves) => format!("mate {moves}"),
+            None => format!("cp {}", line.score),
+        };
+        let pv: Vec<String> = line.pv.iter().map(|&mv| game.coordinates(mv)).collect();
+
+        println!(
+            "info depth {} multipv {} score {score} nodes {} nps {nps} time {ms} pv {}",
+            result.depth,
+            index + 1,
+            result.nodes,
+            pv.join(" ")
+        );
+    }
+}
+
+fn main() {
+    let mut uci = Uci::new();
+
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+
+        if !uci.command(line.trim()) {
+            break;
+        }
+    }
+
+    uci.stop();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn infinite_search_waits_for_stop() {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut uci = Uci::new();
+            // Mate in one, the search finishes straight away but must not answer yet
+            uci.command("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+            uci.command("go infinite");
+            thread::sleep(Duration::from_millis(200));
+            let parked = uci.searching.as_ref().is_some_and(|h| !h.is_finished());
+
+            // A new position stops the search rather than waiting on it forever
+            uci.command("position startpos moves e2e4");
+            let stopped = uci.searching.is_none() && uci.search.is_some();
+            uci.command("stop");
+            sender.send((parked, stopped, uci.game.to_fen())).unwrap();
+        });
+
+        let (parked, stopped, fen) = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(parked);
+        assert!(stopped);
+        assert_eq!(
+            fen,
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+    }
+
+    #[test]
+    fn stop_ends_infinite_search() {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut uci = Uci::new();
+            uci.command("position startpos");
+            uci.command("go infinite");
+            thread::sleep(Duration::from_millis(200));
+            let running = uci.searching.as_ref().is_some_and(|h| !h.is_finished());
+
+            uci.command("stop");
+            let stopped = uci.searching.is_none() && uci.search.is_some();
+            sender.send((running, stopped)).unwrap();
+        });
+
+        // Stop has to end the search still running, not wait on it forever
+        let (running, stopped) = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(running);
+        assert!(stopped);
+    }
+}
@@ -1,59 +1,162 @@
-use crate::game::Square;
+use crate::{piece::Color, square::Square};
 use std::fmt::Display;
 use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Shl, Shr};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct BitBoard(pub u64);
 
+pub const FILE_A: BitBoard = BitBoard(0x0101_0101_0101_0101);
+pub const FILE_H: BitBoard = BitBoard(0x8080_8080_8080_8080);
+
+/// Compass directions on the board, north is towards black's side
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    North,
+    South,
+    East,
+    West,
+    NorthEast,
+    NorthWest,
+    SouthEast,
+    SouthWest,
+}
+
+impl Direction {
+    /// The way a side's pawns move
+    pub fn forward(side: Color) -> Self {
+        match side {
+            Color::White => Direction::North,
+            Color::Black => Direction::South,
+        }
+    }
+}
+
 impl BitBoard {
+    /// Raw shift, squares on the edge files wrap around onto the next rank
     ///* `shift` - How much to shift by
     ///* `positive` - Direction of shift positive for left shift and negative for right shift
     pub fn shift(self, shift: u64, positive: bool) -> BitBoard {
         match positive {
             true => self << shift,
-            false => self.clone() >> shift,
+            false => self >> shift,
         }
     }
 
+    /// Move every square one step, anything that would leave the board is dropped
+    pub fn step(self, dir: Direction) -> BitBoard {
+        let not_a = self.0 & !FILE_A.0;
+        let not_h = self.0 & !FILE_H.0;
+
+        BitBoard(match dir {
+            Direction::North => self.0 << 8,
+            Direction::South => self.0 >> 8,
+            Direction::East => not_h << 1,
+            Direction::West => not_a >> 1,
+            Direction::NorthEast => not_h << 9,
+            Direction::NorthWest => not_a << 7,
+            Direction::SouthEast => not_h >> 7,
+            Direction::SouthWest => not_a >> 9,
+        })
+    }
+
+    pub fn north(self) -> BitBoard {
+        self.step(Direction::North)
+    }
+
+    pub fn south(self) -> BitBoard {
+        self.step(Direction::South)
+    }
+
+    pub fn east(self) -> BitBoard {
+        self.step(Direction::East)
+    }
+
+    pub fn west(self) -> BitBoard {
+        self.step(Direction::West)
+    }
+
+    pub fn north_east(self) -> BitBoard {
+        self.step(Direction::NorthEast)
+    }
+
+    pub fn north_west(self) -> BitBoard {
+        self.step(Direction::NorthWest)
+    }
+
+    pub fn south_east(self) -> BitBoard {
+        self.step(Direction::SouthEast)
+    }
+
+    pub fn south_west(self) -> BitBoard {
+        self.step(Direction::SouthWest)
+    }
+
     pub fn from_square(sqr: Square) -> BitBoard {
         let mut num: u64 = 0;
-        num ^= 1_u64 << sqr.1 * 8 + sqr.0;
+        num ^= 1_u64 << sqr.index();
         BitBoard(num)
     }
 
     // Find the coordinates of all pieces on a bitboard
     pub fn all_coords(&self) -> Vec<Square> {
-        let mut bb = self.clone();
-        let mut coords: Vec<Square> = Vec::new();
+        self.collect()
+    }
 
-        loop {
-            let lsb: u64 = bb.0.trailing_zeros().into();
-            if lsb >= 64 {
-                break;
-            }
+    /// Number of squares set
+    pub fn count(self) -> u32 {
+        self.0.count_ones()
+    }
 
-            coords.push(Square::new(lsb as u8 % 8, lsb as u8 / 8));
-            bb.0 ^= 1 << lsb;
-        }
-        coords
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Lowest square set, a1 side of the board
+    pub fn lsb(self) -> Option<Square> {
+        Square::from_index(self.0.trailing_zeros() as usize)
+    }
+
+    /// Highest square set, h8 side of the board
+    pub fn msb(self) -> Option<Square> {
+        63_usize
+            .checked_sub(self.0.leading_zeros() as usize)
+            .and_then(Square::from_index)
     }
 
     ///  parallel prefix-algorithm
     ///  Mirror a bitboard horizontaly around the center
     pub fn mirror_h(&self) -> BitBoard {
-        let mut bb = self.clone();
-        const k1: u64 = 0x5555555555555555;
-        const k2: u64 = 0x3333333333333333;
-        const k4: u64 = 0x0f0f0f0f0f0f0f0f;
+        let mut bb = *self;
+        const K1: u64 = 0x5555555555555555;
+        const K2: u64 = 0x3333333333333333;
+        const K4: u64 = 0x0f0f0f0f0f0f0f0f;
 
-        bb.0 = ((bb.0 >> 1) & k1) | ((bb.0 & k1) << 1);
-        bb.0 = ((bb.0 >> 2) & k2) | ((bb.0 & k2) << 2);
-        bb.0 = ((bb.0 >> 4) & k4) | ((bb.0 & k4) << 4);
+        bb.0 = ((bb.0 >> 1) & K1) | ((bb.0 & K1) << 1);
+        bb.0 = ((bb.0 >> 2) & K2) | ((bb.0 & K2) << 2);
+        bb.0 = ((bb.0 >> 4) & K4) | ((bb.0 & K4) << 4);
 
         bb
     }
 }
 
+/// Pops squares off from the lowest, without allocating
+impl Iterator for BitBoard {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sqr = self.lsb()?;
+        self.0 &= self.0 - 1;
+        Some(sqr)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.0.count_ones() as usize;
+        (count, Some(count))
+    }
+}
+
+impl ExactSizeIterator for BitBoard {}
+
 impl Display for BitBoard {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for rank in 0..8 {
@@ -62,7 +165,7 @@ impl Display for BitBoard {
                 let chr = if self.0 & mask != 0 { '1' } else { '0' };
                 write!(f, "{chr}")?;
             }
-            write!(f, "\n")?;
+            writeln!(f)?;
         }
         write!(f, "")
     }
@@ -0,0 +1,94 @@
+use crate::{
+    board::BitBoard,
+    game::Game,
+    piece::{Color, Piece},
+    square::Square,
+    state::CastlingRights,
+    zobrist::KEYS,
+};
+
+// Setting up a position by hand. Every edit keeps the bitboards, mailbox and hash in step
+// but takes anything, Game::validate says whether the result is playable. Moves made
+// before an edit can't be undone after it
+impl Game {
+    /// Put a piece on a square, replacing whatever was there
+    pub fn place_piece(&mut self, side: Color, piece: Piece, sqr: Square) {
+        self.take_piece(sqr);
+        self.toggle_piece(side, piece, sqr);
+        self.edited();
+    }
+
+    /// Take the piece off a square, returning it and who it belonged to
+    pub fn clear_square(&mut self, sqr: Square) -> Option<(Color, Piece)> {
+        let cleared = self.take_piece(sqr);
+        self.edited();
+        cleared
+    }
+
+    /// Take every piece off the board, along with the castling rights and en passant
+    /// square that went with them. Pockets and the side to move are kept
+    pub fn clear_board(&mut self) {
+        for sqr in Square::ALL {
+            self.take_piece(sqr);
+        }
+        self.set_castling_rights(CastlingRights::new());
+        self.set_en_passant(None);
+        self.edited();
+    }
+
+    /// Change the side to move. Any en passant square goes, it only belonged to the side
+    /// that was to move
+    pub fn set_turn(&mut self, side: Color) {
+        if self.turn != side {
+            self.turn = side;
+            self.state_mut().hash ^= KEYS.side();
+        }
+        self.set_en_passant(None);
+        self.edited();
+    }
+
+    /// Give or take away one castling right. The right goes with the outermost rook on
+    /// that side of the king, or the usual corner if there isn't one yet
+    pub fn set_castling(&mut self, side: Color, kingside: bool, allowed: bool) {
+        if allowed {
+            let corner = if kingside { 7 } else { 0 };
+            let file = self.outermost_rook(side, kingside).unwrap_or(corner);
+            self.allow_castling(side, kingside, file);
+        } else {
+            let mut rights = self.state().castling_rights;
+            rights.remove(if kingside {
+                CastlingRights::kingside(side)
+            } else {
+                CastlingRights::queenside(side)
+            });
+            self.set_castling_rights(rights);
+        }
+        self.edited();
+    }
+
+    pub(crate) fn set_castling_rights(&mut self, rights: CastlingRights) {
+        let state = self.state_mut();
+        state.hash ^= KEYS.castling(state.castling_rights.bits()) ^ KEYS.castling(rights.bits());
+        state.castling_rights = rights;
+    }
+
+    fn set_en_passant(&mut self, sqr: Option<Square>) {
+        let state = self.state_mut();
+        state.hash ^= KEYS.en_passant(state.en_passant) ^ KEYS.en_passant(sqr);
+        state.en_passant = sqr;
+    }
+
+    fn take_piece(&mut self, sqr: Square) -> Option<(Color, Piece)> {
+        let taken = self.piece_at(sqr);
+        if let Some((side, piece)) = taken {
+            self.toggle_piece(side, piece, sqr);
+            self.state_mut().promoted &= !BitBoard::from_square(sqr);
+        }
+        taken
+    }
+
+    fn edited(&mut self) {
+        self.clear_history();
+        self.update_checkers();
+    }
+}
@@ -0,0 +1,355 @@
+use crate::{
+    board::BitBoard,
+    game::{Game, FULL_COL},
+    piece::{Color, Piece},
+    square::Square,
+};
+
+// Middlegame and endgame values indexed by Piece
+pub const PIECE_VALUES_MG: [i32; 6] = [82, 477, 337, 365, 1025, 0];
+pub const PIECE_VALUES_EG: [i32; 6] = [94, 512, 281, 297, 936, 0];
+
+// Game phase each piece is worth, a full board adds up to MAX_PHASE
+const PHASE_WEIGHTS: [i32; 6] = [0, 2, 1, 1, 4, 0];
+const MAX_PHASE: i32 = 24;
+
+const DOUBLED_PAWN: (i32, i32) = (-10, -20);
+const ISOLATED_PAWN: (i32, i32) = (-10, -15);
+// Indexed by how many ranks the pawn has advanced
+const PASSED_PAWN: [(i32, i32); 8] = [
+    (0, 0),
+    (5, 10),
+    (10, 15),
+    (15, 25),
+    (25, 45),
+    (40, 70),
+    (60, 110),
+    (0, 0),
+];
+
+// Per square a piece can move to, indexed by Piece
+const MOBILITY: [(i32, i32); 6] = [(0, 0), (2, 4), (4, 4), (4, 5), (1, 2), (0, 0)];
+
+const PAWN_SHIELD: i32 = 12;
+// Bonus for each attack on a square next to the enemy king
+const KING_ZONE_ATTACK: i32 = 8;
+
+// Tables are written from white's point of view with a8 in the top left,
+// so a square is looked up with its index flipped for white
+#[rustfmt::skip]
+const PAWN_MG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     10,  10,  20,  30,  30,  20,  10,  10,
+      5,   5,  10,  25,  25,  10,   5,   5,
+      0,   0,   0,  20,  20,   0,   0,   0,
+      5,  -5, -10,   0,   0, -10,  -5,   5,
+      5,  10,  10, -20, -20,  10,  10,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const PAWN_EG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     80,  80,  80,  80,  80,  80,  80,  80,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     30,  30,  30,  30,  30,  30,  30,  30,
+     15,  15,  15,  15,  15,  15,  15,  15,
+      5,   5,   5,   5,   5,   5,   5,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_MG: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const KNIGHT_EG: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,  -5,   0,   0,  -5, -20, -40,
+    -30,  -5,  10,  15,  15,  10,  -5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,  -5,  10,  15,  15,  10,  -5, -30,
+    -40, -20,  -5,   0,   0,  -5, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const BISHOP_MG: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const BISHOP_EG: [i32; 64] = [
+    -15, -10, -10, -10, -10, -10, -10, -15,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -15, -10, -10, -10, -10, -10, -10, -15,
+];
+
+#[rustfmt::skip]
+const ROOK_MG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const ROOK_EG: [i32; 64] = [
+      5,   5,   5,   5,   5,   5,   5,   5,
+     10,  10,  10,  10,  10,  10,  10,  10,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+];
+
+#[rustfmt::skip]
+const QUEEN_MG: [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const QUEEN_EG: [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+    -10,   5,  10,  10,  10,  10,   5, -10,
+     -5,   5,  10,  15,  15,  10,   5,  -5,
+     -5,   5,  10,  15,  15,  10,   5,  -5,
+    -10,   5,  10,  10,  10,  10,   5, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const KING_MG: [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+     20,  20,   0,   0,   0,   0,  20,  20,
+     20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+#[rustfmt::skip]
+const KING_EG: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+// Indexed by Piece
+const PST_MG: [[i32; 64]; 6] = [PAWN_MG, ROOK_MG, KNIGHT_MG, BISHOP_MG, QUEEN_MG, KING_MG];
+const PST_EG: [[i32; 64]; 6] = [PAWN_EG, ROOK_EG, KNIGHT_EG, BISHOP_EG, QUEEN_EG, KING_EG];
+
+/// Score a position in centipawns from the side to move's point of view
+pub fn evaluate(game: &Game) -> i32 {
+    let mut mg = [0; 2];
+    let mut eg = [0; 2];
+    let mut phase = 0;
+
+    for side in Color::ALL {
+        let s = side as usize;
+
+        for piece in Piece::ALL {
+            let p = piece as usize;
+
+            for sqr in game.piece_bb(side, piece) {
+                let index = table_index(sqr, side);
+                mg[s] += PIECE_VALUES_MG[p] + PST_MG[p][index];
+                eg[s] += PIECE_VALUES_EG[p] + PST_EG[p][index];
+                phase += PHASE_WEIGHTS[p];
+            }
+        }
+
+        // Pieces in a Crazyhouse pocket are worth the same as on the board
+        for piece in Piece::POCKET {
+            let count = game.pocket(side, piece) as i32;
+            mg[s] += count * PIECE_VALUES_MG[piece as usize];
+            eg[s] += count * PIECE_VALUES_EG[piece as usize];
+        }
+
+        for (extra_mg, extra_eg) in [
+            pawn_structure(game, side),
+            mobility_and_king_attacks(game, side),
+        ] {
+            mg[s] += extra_mg;
+            eg[s] += extra_eg;
+        }
+        mg[s] += king_shelter(game, side);
+    }
+
+    // Blend between the middlegame and endgame scores by how much material is left
+    let phase = phase.min(MAX_PHASE);
+    let mg_score = mg[0] - mg[1];
+    let eg_score = eg[0] - eg[1];
+    let score = (mg_score * phase + eg_score * (MAX_PHASE - phase)) / MAX_PHASE;
+
+    if game.turn() == Color::White {
+        score
+    } else {
+        -score
+    }
+}
+
+// Position in the piece square tables
+fn table_index(sqr: Square, side: Color) -> usize {
+    match side {
+        Color::White => sqr.flip_rank().index(),
+        Color::Black => sqr.index(),
+    }
+}
+
+// Squares in front of a pawn on its own and both neighbouring files
+fn passed_mask(sqr: Square, side: Color) -> BitBoard {
+    let files = (FULL_COL << sqr.file()) | neighbour_files(sqr);
+
+    let ahead = match side {
+        Color::White => u64::MAX
+            .checked_shl((sqr.rank() as u32 + 1) * 8)
+            .unwrap_or(0),
+        Color::Black => (1_u64 << (sqr.rank() as u32 * 8)) - 1,
+    };
+
+    BitBoard(files & ahead)
+}
+
+// Files either side of a square
+fn neighbour_files(sqr: Square) -> u64 {
+    let mut files = 0;
+    if sqr.file() > 0 {
+        files |= FULL_COL << (sqr.file() - 1);
+    }
+    if sqr.file() < 7 {
+        files |= FULL_COL << (sqr.file() + 1);
+    }
+    files
+}
+
+// Doubled, isolated and passed pawns
+fn pawn_structure(game: &Game, side: Color) -> (i32, i32) {
+    let pawns = game.piece_bb(side, Piece::Pawn);
+    let enemy_pawns = game.piece_bb(!side, Piece::Pawn);
+    let (mut mg, mut eg) = (0, 0);
+
+    for file in 0..8 {
+        let count = (pawns.0 & (FULL_COL << file)).count_ones() as i32;
+        if count > 1 {
+            mg += DOUBLED_PAWN.0 * (count - 1);
+            eg += DOUBLED_PAWN.1 * (count - 1);
+        }
+    }
+
+    for sqr in pawns {
+        if pawns.0 & neighbour_files(sqr) == 0 {
+            mg += ISOLATED_PAWN.0;
+            eg += ISOLATED_PAWN.1;
+        }
+
+        if (passed_mask(sqr, side) & enemy_pawns).0 == 0 {
+            let advanced = match side {
+                Color::White => sqr.rank(),
+                Color::Black => 7 - sqr.rank(),
+            };
+            mg += PASSED_PAWN[advanced as usize].0;
+            eg += PASSED_PAWN[advanced as usize].1;
+        }
+    }
+
+    (mg, eg)
+}
+
+// Squares each piece can reach, and how many of them are next to the enemy king
+fn mobility_and_king_attacks(game: &Game, side: Color) -> (i32, i32) {
+    let (mut mg, mut eg) = (0, 0);
+    let mut king_attacks = 0;
+
+    let king_zone = match game.piece_bb(!side, Piece::King).lsb() {
+        Some(king) => game.legal_king_moves(king, !side) | BitBoard::from_square(king),
+        None => BitBoard(0),
+    };
+
+    for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+        for sqr in game.piece_bb(side, piece) {
+            let moves = match piece {
+                Piece::Knight => game.legal_knight_moves(sqr, side),
+                Piece::Bishop => game.legal_bishop_moves(sqr, side),
+                Piece::Rook => game.legal_rook_moves(sqr, side),
+                _ => game.legal_queen_moves(sqr, side),
+            };
+
+            let count = moves.count() as i32;
+            mg += MOBILITY[piece as usize].0 * count;
+            eg += MOBILITY[piece as usize].1 * count;
+            king_attacks += (moves & king_zone).count() as i32;
+        }
+    }
+
+    mg += KING_ZONE_ATTACK * king_attacks;
+    (mg, eg)
+}
+
+// Friendly pawns directly in front of the king, only matters in the middlegame
+fn king_shelter(game: &Game, side: Color) -> i32 {
+    let Some(king) = game.piece_bb(side, Piece::King).lsb() else {
+        return 0;
+    };
+
+    // Only the two ranks in front of the king
+    let rank = match side {
+        Color::White => king.rank() as i32 + 1,
+        Color::Black => king.rank() as i32 - 2,
+    };
+    let ranks = if (0..7).contains(&rank) {
+        BitBoard(0xFFFF_u64 << (rank * 8))
+    } else {
+        BitBoard(0)
+    };
+
+    (passed_mask(king, side) & ranks & game.piece_bb(side, Piece::Pawn))
+        .0
+        .count_ones() as i32
+        * PAWN_SHIELD
+}
@@ -0,0 +1,323 @@
+use std::fmt::Display;
+
+use crate::{
+    board::BitBoard,
+    game::Game,
+    piece::{Color, Piece},
+    square::Square,
+    state::CastlingRights,
+};
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FenError {
+    MissingField(&'static str),
+    InvalidPiece(char),
+    InvalidBoard,
+    InvalidTurn,
+    InvalidCastling,
+    InvalidEnPassant,
+    InvalidNumber,
+    InvalidPocket,
+}
+
+impl Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FenError::MissingField(field) => write!(f, "FEN is missing the {field} field"),
+            FenError::InvalidPiece(chr) => write!(f, "'{chr}' is not a piece"),
+            FenError::InvalidBoard => write!(f, "FEN board does not describe 8 ranks of 8 files"),
+            FenError::InvalidTurn => write!(f, "Side to move must be w or b"),
+            FenError::InvalidCastling => write!(f, "Invalid castling rights"),
+            FenError::InvalidEnPassant => write!(f, "Invalid en passant square"),
+            FenError::InvalidNumber => write!(f, "Invalid move counter"),
+            FenError::InvalidPocket => write!(f, "Pockets can only hold pawns to queens"),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+impl Game {
+    /// Load a position from Forsyth-Edwards Notation. Crazyhouse pockets can follow the
+    /// board in brackets or as a ninth rank, e.g. RNBQKBNR[Qp] or RNBQKBNR/Qp, and a ~
+    /// after a piece marks it as promoted
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let mut game = Game::new();
+        let mut fields = fen.split_whitespace();
+
+        let board = fields.next().ok_or(FenError::MissingField("board"))?;
+        let (board, pockets) = match board.strip_suffix(']').and_then(|b| b.split_once('[')) {
+            Some((board, pockets)) => (board, Some(pockets)),
+            None => (board, None),
+        };
+        let mut ranks: Vec<&str> = board.split('/').collect();
+        let pockets = match (pockets, ranks.len()) {
+            (Some(pockets), 8) => Some(pockets),
+            (None, 9) => ranks.pop(),
+            (None, 8) => None,
+            _ => return Err(FenError::InvalidBoard),
+        };
+
+        // FEN starts from the 8th rank
+        for (i, row) in ranks.iter().enumerate() {
+            let rank = 7 - i as u8;
+            let mut file: u8 = 0;
+
+            for chr in row.chars() {
+                if let Some(skip) = chr.to_digit(10) {
+                    file = file.saturating_add(skip as u8);
+                } else if chr == '~' {
+                    // Follows the piece it marks
+                    let sqr = file
+                        .checked_sub(1)
+                        .and_then(|file| Square::new(file, rank))
+                        .filter(|&sqr| game.piece_at(sqr).is_some())
+                        .ok_or(FenError::InvalidBoard)?;
+                    game.set_promoted(sqr);
+                } else {
+                    let piece = Piece::from_char(chr).ok_or(FenError::InvalidPiece(chr))?;
+                    let side = if chr.is_ascii_uppercase() {
+                        Color::White
+                    } else {
+                        Color::Black
+                    };
+
+                    let sqr = Square::new(file, rank).ok_or(FenError::InvalidBoard)?;
+                    game.toggle_piece(side, piece, sqr);
+                    file += 1;
+                }
+            }
+
+            if file != 8 {
+                return Err(FenError::InvalidBoard);
+            }
+        }
+
+        for chr in pockets
+            .unwrap_or_default()
+            .chars()
+            .filter(|&chr| chr != '-')
+        {
+            let piece = Piece::from_char(chr)
+                .filter(|&piece| piece != Piece::King)
+                .ok_or(FenError::InvalidPocket)?;
+            let side = if chr.is_ascii_uppercase() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            game.add_to_pocket(side, piece);
+        }
+
+        game.set_turn(match fields.next().ok_or(FenError::MissingField("turn"))? {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err(FenError::InvalidTurn),
+        });
+
+        let castling = fields.next().ok_or(FenError::MissingField("castling"))?;
+        if castling != "-" {
+            for chr in castling.chars() {
+                game.add_castling(chr)?;
+            }
+        }
+
+        let state = game.state_mut();
+        let en_passant = fields.next().ok_or(FenError::MissingField("en passant"))?;
+        if en_passant != "-" {
+            state.en_passant = Some(en_passant.parse().map_err(|_| FenError::InvalidEnPassant)?);
+        }
+
+        // Move counters are often left off so default them
+        if let Some(clock) = fields.next() {
+            state.halfmove_clock = clock.parse().map_err(|_| FenError::InvalidNumber)?;
+        }
+        if let Some(number) = fields.next() {
+            state.fullmove_number = number.parse().map_err(|_| FenError::InvalidNumber)?;
+        }
+
+        game.state_mut().hash = game.compute_hash();
+        game.update_checkers();
+        Ok(game)
+    }
+
+    // Castling right from either X-FEN, where KQkq mean the outermost rook on that side of
+    // the king, or Shredder-FEN which gives the rook's file e.g. HAha
+    fn add_castling(&mut self, chr: char) -> Result<(), FenError> {
+        let side = if chr.is_ascii_uppercase() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let king = self
+            .piece_bb(side, Piece::King)
+            .lsb()
+            .filter(|king| king.rank() == side.back_rank())
+            .map(|king| king.file());
+
+        let (kingside, file) = match (chr.to_ascii_lowercase(), king) {
+            // Without a king or rook where they should be it was never going to castle, the
+            // usual files are assumed to keep the right around
+            ('k', _) => (true, self.outermost_rook(side, true).unwrap_or(7)),
+            ('q', _) => (false, self.outermost_rook(side, false).unwrap_or(0)),
+            (file @ 'a'..='h', Some(king)) => {
+                let file = file as u8 - b'a';
+                if file == king {
+                    return Err(FenError::InvalidCastling);
+                }
+                (file > king, file)
+            }
+            _ => return Err(FenError::InvalidCastling),
+        };
+
+        self.allow_castling(side, kingside, file);
+        Ok(())
+    }
+
+    // Give a side the right to castle with the rook on a file, which makes it Chess960
+    // unless the king and rook are on their usual squares
+    pub(crate) fn allow_castling(&mut self, side: Color, kingside: bool, file: u8) {
+        let king = self
+            .piece_bb(side, Piece::King)
+            .lsb()
+            .filter(|king| king.rank() == side.back_rank())
+            .map(|king| king.file());
+
+        self.set_castling_file(side, kingside, file);
+        if king.is_some_and(|king| king != 4) || file != if kingside { 7 } else { 0 } {
+            self.set_chess960(true);
+        }
+
+        let mut rights = self.state().castling_rights;
+        rights.add(if kingside {
+            CastlingRights::kingside(side)
+        } else {
+            CastlingRights::queenside(side)
+        });
+        self.set_castling_rights(rights);
+    }
+
+    // File of the rook furthest from the king on one side of it, along the back rank
+    pub(crate) fn outermost_rook(&self, side: Color, kingside: bool) -> Option<u8> {
+        let rank = side.back_rank();
+        let king = self.piece_bb(side, Piece::King).lsb()?;
+        if king.rank() != rank {
+            return None;
+        }
+
+        let is_rook = |file: &u8| {
+            self.piece_at(Square::new(*file, rank).unwrap()) == Some((side, Piece::Rook))
+        };
+        if kingside {
+            (king.file() + 1..8).rev().find(is_rook)
+        } else {
+            (0..king.file()).find(is_rook)
+        }
+    }
+
+    // Both pockets as FEN letters, white's first e.g. QNpp
+    pub(crate) fn pocket_letters(&self) -> String {
+        let mut letters = String::new();
+        for side in Color::ALL {
+            for piece in Piece::POCKET {
+                for _ in 0..self.pocket(side, piece) {
+                    letters.push(letter(side, piece));
+                }
+            }
+        }
+        letters
+    }
+
+    /// Write the position out in Forsyth-Edwards Notation, with the pockets in brackets
+    /// after the board if the game has any
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        let pockets = self.has_pockets();
+
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                let sqr = Square::new(file, rank).unwrap();
+                match self.piece_at(sqr) {
+                    Some((side, piece)) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(letter(side, piece));
+                        if pockets && !(self.promoted() & BitBoard::from_square(sqr)).is_empty() {
+                            fen.push('~');
+                        }
+                    }
+                    None => empty += 1,
+                }
+            }
+
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        if pockets {
+            fen.push('[');
+            fen.push_str(&self.pocket_letters());
+            fen.push(']');
+        }
+
+        let state = self.state();
+        let turn = match self.turn() {
+            Color::White => 'w',
+            Color::Black => 'b',
+        };
+
+        let mut castling = String::new();
+        for (side, kingside, right, chr) in [
+            (Color::White, true, CastlingRights::WHITE_KING, 'K'),
+            (Color::White, false, CastlingRights::WHITE_QUEEN, 'Q'),
+            (Color::Black, true, CastlingRights::BLACK_KING, 'k'),
+            (Color::Black, false, CastlingRights::BLACK_QUEEN, 'q'),
+        ] {
+            if !state.castling_rights.has(right) {
+                continue;
+            }
+
+            // X-FEN, the rook's file is only needed when another rook is further out
+            let file = self.castling_file(side, kingside);
+            match self.outermost_rook(side, kingside) {
+                Some(outer) if outer != file => {
+                    let letter = (b'a' + file) as char;
+                    castling.push(match side {
+                        Color::White => letter.to_ascii_uppercase(),
+                        Color::Black => letter,
+                    });
+                }
+                _ => castling.push(chr),
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        let en_passant = state
+            .en_passant
+            .map_or("-".to_string(), |sqr| sqr.to_string());
+
+        format!(
+            "{fen} {turn} {castling} {en_passant} {} {}",
+            state.halfmove_clock, state.fullmove_number
+        )
+    }
+}
+
+// FEN letter, uppercase for white
+fn letter(side: Color, piece: Piece) -> char {
+    match side {
+        Color::White => piece.to_char().to_ascii_uppercase(),
+        Color::Black => piece.to_char(),
+    }
+}
@@ -1,28 +1,71 @@
-use core::panic;
+use std::fmt::Display;
 
-use crate::{board::BitBoard, state::State};
+use crate::{
+    attacks,
+    board::BitBoard,
+    movelist::MoveList,
+    piece::{Color, Piece},
+    square::Square,
+    state::{CastlingRights, State},
+    variant::{Variant, STANDARD},
+    zobrist::KEYS,
+};
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LegalMove {
     Move,
     Attack,
-    Promotion, // engine should always promote to knight TODO
+    Promotion,
     Castle,
-    EnPassant, // not real move
+    EnPassant,   // not real move
+    Drop(Piece), // Crazyhouse piece put down from the pocket
 }
 
-#[derive(Clone, Copy, Debug)]
-pub struct Square(pub u8, pub u8);
-impl Square {
-    pub fn new(file: u8, rank: u8) -> Self {
-        Self(file, rank)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Move {
+    pub from: Square,
+    pub to: Square,
+    pub kind: LegalMove,
+    // Piece the pawn turns into, only set for promotions
+    pub promotion: Option<Piece>,
+}
+
+impl Move {
+    pub fn new(from: Square, to: Square, kind: LegalMove) -> Self {
+        Self {
+            from,
+            to,
+            kind,
+            promotion: None,
+        }
+    }
+
+    /// Put a piece from the pocket down on a square, from is the same as to
+    pub fn drop(piece: Piece, to: Square) -> Self {
+        Self::new(to, to, LegalMove::Drop(piece))
+    }
+
+    /// Piece taken from the pocket if this is a drop
+    pub fn dropped(&self) -> Option<Piece> {
+        match self.kind {
+            LegalMove::Drop(piece) => Some(piece),
+            _ => None,
+        }
     }
 }
 
-impl std::ops::Add for Square {
-    type Output = Self;
+/// Coordinate notation e.g. e2e4, e7e8q or N@f3 for a drop
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(piece) = self.dropped() {
+            return write!(f, "{}@{}", piece.to_char().to_ascii_uppercase(), self.to);
+        }
+        write!(f, "{}{}", self.from, self.to)?;
 
-    fn add(self, rhs: Self) -> Self::Output {
-        Self(self.0 + rhs.0, self.1 + rhs.1)
+        match self.promotion {
+            Some(piece) => write!(f, "{piece}"),
+            None => Ok(()),
+        }
     }
 }
 
@@ -30,329 +73,1071 @@ pub const FULL_ROW: u64 = 2_u64.pow(8) - 1;
 pub const FULL_COL: u64 = 0x0101010101010101;
 pub const ROW: u64 = 8;
 pub const COL: u64 = 8;
+// First and last ranks, where pawns can't be dropped
+const BACK_RANKS: BitBoard = BitBoard(FULL_ROW | FULL_ROW << 56);
 
-const ROOK_OFFSETS: [(i8, i8); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
-const BISHOP_OFFSETS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
-// Only contains right side as will be mirrored to the other side
-const KNIGHT_OFFSETS: [(i8, i8); 8] = [
-    (1, 2),
-    (2, 1),
-    (2, -1),
-    (1, -2),
-    (-1, -2),
-    (-2, -1),
-    (-2, 1),
-    (-1, 2),
-];
-const KING_OFFSETS: [(i8, i8); 8] = [
+/// Chess960 start positions are numbered 0 to 959, the standard setup is 518
+pub const CHESS960_POSITIONS: u16 = 960;
+pub const STANDARD_POSITION: u16 = 518;
+
+// Ways to place two knights on the five squares left once the bishops and queen are down
+const KNIGHTS: [(usize, usize); 10] = [
     (0, 1),
-    (1, 1),
-    (1, 0),
-    (1, -1),
-    (0, -1),
-    (-1, -1),
-    (-1, 0),
-    (-1, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
 ];
-pub struct Pieces {}
-impl Pieces {
-    pub const PAWN: usize = 0;
-    pub const ROOK: usize = 1;
-    pub const KNIGHT: usize = 2;
-    pub const BISHOP: usize = 3;
-    pub const QUEEN: usize = 4;
-    pub const KING: usize = 5;
-    pub const EMPTY: usize = 6;
-
-    pub fn all() -> Vec<usize> {
-        vec![
-            Self::PAWN,
-            Self::ROOK,
-            Self::KNIGHT,
-            Self::BISHOP,
-            Self::QUEEN,
-            Self::KING,
-        ]
-    }
-}
 
-pub struct Sides {}
-impl Sides {
-    pub const WHITE: usize = 0;
-    pub const BLACK: usize = 1;
-}
+/// Back rank of a Chess960 start position from its number, using Scharnagl's numbering.
+/// Numbers past 959 wrap around
+pub fn chess960_back_rank(number: u16) -> [Piece; 8] {
+    let mut rank = [None; 8];
+    let mut n = number as usize % CHESS960_POSITIONS as usize;
+
+    // One bishop on each colour, b1 is a light square
+    rank[n % 4 * 2 + 1] = Some(Piece::Bishop);
+    n /= 4;
+    rank[n % 4 * 2] = Some(Piece::Bishop);
+    n /= 4;
 
-const NUM_PIECES: usize = 8;
-const NUM_SIDES: usize = 2;
+    // Everything else goes on the nth square still empty
+    let mut place = |piece, nth: usize| {
+        let file = (0..8).filter(|&f| rank[f].is_none()).nth(nth).unwrap();
+        rank[file] = Some(piece);
+    };
+    place(Piece::Queen, n % 6);
+    n /= 6;
 
+    // Placing the first knight shifts the empty squares along by one
+    let (first, second) = KNIGHTS[n];
+    place(Piece::Knight, first);
+    place(Piece::Knight, second - 1);
+
+    // The king always ends up between the rooks
+    place(Piece::Rook, 0);
+    place(Piece::King, 0);
+    place(Piece::Rook, 0);
+
+    rank.map(Option::unwrap)
+}
+
+#[derive(Clone)]
 pub struct Game {
-    pub turn: usize,
-    pub pieces: [[BitBoard; NUM_PIECES]; NUM_SIDES],
-    pub sides: [BitBoard; NUM_SIDES],
+    // Only changed by moves and set_turn, which keep the hash and checkers in step
+    pub(crate) turn: Color,
+    pieces: [[BitBoard; Piece::COUNT]; Color::COUNT],
+    sides: [BitBoard; Color::COUNT],
+    // What is on each square, kept in step with the bitboards
+    mailbox: [Option<(Color, Piece)>; 64],
     state: State,
+    // States before each move played so far, popped by unmake_move
+    history: Vec<State>,
+    // Files the rooks castle from for each side, king side then queen side. Fixed for the
+    // whole game, a1 and h1 unless it is Chess960
+    castling_files: [[u8; 2]; Color::COUNT],
+    // Castling moves are written as the king taking its rook
+    chess960: bool,
+    variant: &'static dyn Variant,
+    // Pieces the variant took off the board, State::removed says how many for each move
+    removed: Vec<(Color, Piece, Square)>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Game {
     pub fn new() -> Self {
         Self {
-            pieces: [[BitBoard(0); NUM_PIECES]; NUM_SIDES],
-            sides: [BitBoard(0); NUM_SIDES],
+            pieces: [[BitBoard(0); Piece::COUNT]; Color::COUNT],
+            sides: [BitBoard(0); Color::COUNT],
+            mailbox: [None; 64],
             state: State::new(),
-            turn: 0,
+            history: Vec::new(),
+            turn: Color::White,
+            castling_files: [[7, 0]; Color::COUNT],
+            chess960: false,
+            variant: &STANDARD,
+            removed: Vec::new(),
         }
     }
 
-    /// Initialize default chess board
-    pub fn init(&mut self) {
-        self.pieces[Sides::WHITE][Pieces::PAWN].0 ^= FULL_ROW << ROW;
-        self.pieces[Sides::BLACK][Pieces::PAWN].0 ^= FULL_ROW << ((ROW - 2) * 8);
+    /// Set up the start position with the given Chess960 number, STANDARD_POSITION for
+    /// normal chess. Numbers past 959 wrap around like chess960_back_rank. Expects an empty
+    /// board
+    pub fn init(&mut self, number: u16) {
+        let number = number % CHESS960_POSITIONS;
+        let back_rank = chess960_back_rank(number);
+
+        for side in Color::ALL {
+            let rank = side.back_rank();
+            let pawns = (rank as i8 + side.forward()) as u8;
 
-        for i in 0..3 {
-            self.pieces[Sides::WHITE][1 + i].0 ^= 2_u64.pow(i as u32) + 2_u64.pow(7 - i as u32);
-            self.pieces[Sides::BLACK][1 + i].0 ^=
-                (2_u64.pow(i as u32) + 2_u64.pow(7 - i as u32)) << (7 * ROW);
+            for (file, &piece) in back_rank.iter().enumerate() {
+                self.toggle_piece(side, piece, Square::new(file as u8, rank).unwrap());
+                self.toggle_piece(side, Piece::Pawn, Square::new(file as u8, pawns).unwrap());
+            }
         }
 
-        self.pieces[Sides::WHITE][Pieces::QUEEN].0 ^= 2_u64.pow(7 - 4);
-        self.pieces[Sides::BLACK][Pieces::QUEEN].0 ^= 2_u64.pow(7 - 4) << (7 * ROW);
+        let rooks: Vec<u8> = (0..8)
+            .filter(|&file| back_rank[file as usize] == Piece::Rook)
+            .collect();
+        self.castling_files = [[rooks[1], rooks[0]]; Color::COUNT];
+        self.chess960 = number != STANDARD_POSITION;
 
-        self.pieces[Sides::WHITE][Pieces::KING].0 ^= 2_u64.pow(7 - 3);
-        self.pieces[Sides::BLACK][Pieces::KING].0 ^= 2_u64.pow(7 - 3) << (7 * ROW);
+        self.state.castling_rights = CastlingRights::all();
+        self.state.hash = self.compute_hash();
+        self.update_checkers();
+    }
 
-        for i in 0..2 {
-            for j in 0..6 {
-                self.sides[i].0 ^= self.pieces[i][j].0;
-            }
-        }
+    /// Whether castling moves are written the Chess960 way
+    pub fn is_chess960(&self) -> bool {
+        self.chess960
     }
 
-    fn find_piece_type(&self, sqr: Square) -> Option<usize> {
-        let bb = BitBoard::from_square(sqr);
+    /// Positions from a Chess960 start number or with unusual castling rooks turn this on,
+    /// a GUI playing 960 from the standard setup has to turn it on itself
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+    }
 
-        if (bb.0 & (self.sides[Sides::WHITE].0 | self.sides[Sides::BLACK].0)) == 0 {
-            return None;
-        }
+    /// Rules the game is played by
+    pub fn variant(&self) -> &'static dyn Variant {
+        self.variant
+    }
+
+    /// Play by a variant's rules, set before any moves are made
+    pub fn set_variant(&mut self, variant: &'static dyn Variant) {
+        self.variant = variant;
+        self.update_checkers();
+    }
 
-        Pieces::all().iter().position(|&piece| {
-            (bb.0 & (self.pieces[Sides::WHITE][piece] | self.pieces[Sides::BLACK][piece]).0) > 0
+    /// Whether the game is played by the normal rules, books and tablebases only know those
+    pub fn is_standard(&self) -> bool {
+        self.variant.name() == STANDARD.name()
+    }
+
+    /// Side that has won by the variant's rules, e.g. a king reaching the hill. Checkmate
+    /// isn't included, that shows up as no legal moves
+    pub fn winner(&self) -> Option<Color> {
+        self.variant.winner(self)
+    }
+
+    /// How many of a piece a side has in its pocket, ready to drop
+    pub fn pocket(&self, side: Color, piece: Piece) -> u8 {
+        self.state.pockets[side as usize][piece as usize]
+    }
+
+    /// Give a side a piece to drop, the capture in Crazyhouse or a piece passed over by
+    /// the partner in Bughouse
+    pub fn add_to_pocket(&mut self, side: Color, piece: Piece) {
+        assert!(piece != Piece::King, "Kings can't go in a pocket");
+        let count = &mut self.state.pockets[side as usize][piece as usize];
+        self.state.hash ^= KEYS.pocket(side, piece, *count);
+        *count += 1;
+        self.state.hash ^= KEYS.pocket(side, piece, *count);
+    }
+
+    fn take_from_pocket(&mut self, side: Color, piece: Piece) {
+        let count = &mut self.state.pockets[side as usize][piece as usize];
+        self.state.hash ^= KEYS.pocket(side, piece, *count);
+        *count -= 1;
+        self.state.hash ^= KEYS.pocket(side, piece, *count);
+    }
+
+    /// Whether the game has pockets to show, either the variant drops pieces or some have
+    /// been given out anyway
+    pub fn has_pockets(&self) -> bool {
+        self.variant.drops() || self.state.pockets.iter().flatten().any(|&count| count > 0)
+    }
+
+    /// Pieces on the board that were pawns before promoting
+    pub fn promoted(&self) -> BitBoard {
+        self.state.promoted
+    }
+
+    pub(crate) fn set_promoted(&mut self, sqr: Square) {
+        self.state.promoted |= BitBoard::from_square(sqr);
+    }
+
+    /// What the piece taken by the last move is worth in a pocket, promoted pieces go back
+    /// to being pawns
+    pub fn pocketed(&self) -> Option<Piece> {
+        let captured = self.state.captured?;
+        Some(if self.state.captured_promoted {
+            Piece::Pawn
+        } else {
+            captured
         })
     }
 
-    fn find_side(&self, sqr: Square) -> Option<usize> {
-        let bb = BitBoard::from_square(sqr);
+    /// File of the rook a castling right belongs to
+    pub fn castling_file(&self, side: Color, kingside: bool) -> u8 {
+        self.castling_files[side as usize][!kingside as usize]
+    }
+
+    pub(crate) fn set_castling_file(&mut self, side: Color, kingside: bool, file: u8) {
+        self.castling_files[side as usize][!kingside as usize] = file;
+    }
+
+    // Drop the moves played so far, they can't be undone once the board has been edited
+    pub(crate) fn clear_history(&mut self) {
+        self.history.clear();
+        self.removed.clear();
+    }
+
+    /// Bitboard of one kind of piece for one side
+    pub fn piece_bb(&self, color: Color, piece: Piece) -> BitBoard {
+        self.pieces[color as usize][piece as usize]
+    }
+
+    /// Bitboard of every piece belonging to a side
+    pub fn color_bb(&self, color: Color) -> BitBoard {
+        self.sides[color as usize]
+    }
+
+    /// Side to move
+    pub fn turn(&self) -> Color {
+        self.turn
+    }
+
+    /// Zobrist key identifying the current position
+    pub fn hash(&self) -> u64 {
+        self.state.hash
+    }
+
+    // Build the Zobrist key from scratch, make_move keeps it updated after this
+    pub(crate) fn compute_hash(&self) -> u64 {
+        let mut hash = 0;
+
+        for color in Color::ALL {
+            for piece in Piece::ALL {
+                for sqr in self.piece_bb(color, piece) {
+                    hash ^= KEYS.piece(color, piece, sqr);
+                }
+            }
+        }
+
+        if self.turn == Color::Black {
+            hash ^= KEYS.side();
+        }
 
-        for i in [Sides::WHITE, Sides::BLACK] {
-            if (self.sides[i].0 & bb.0) > 0 {
-                return Some(i);
+        for color in Color::ALL {
+            hash ^= KEYS.checks(color, self.state.checks[color as usize]);
+
+            for piece in Piece::POCKET {
+                hash ^= KEYS.pocket(color, piece, self.pocket(color, piece));
             }
         }
 
-        None
+        hash ^ KEYS.castling(self.state.castling_rights.bits())
+            ^ KEYS.en_passant(self.state.en_passant)
     }
 
-    /// Function to determine wether a board is in check
-    pub fn in_check(&self, side: usize) -> bool {
-        let enemy = if side == Sides::WHITE {
-            Sides::WHITE
-        } else {
-            Sides::BLACK
-        };
+    /// Whether the current position has already appeared since the last irreversible move
+    pub fn is_repetition(&self) -> bool {
+        self.history
+            .iter()
+            .rev()
+            .take(self.state.halfmove_clock as usize)
+            .skip(1)
+            .step_by(2)
+            .any(|state| state.hash == self.state.hash)
+    }
 
-        let mut bb = BitBoard(0);
-        for piece in self.pieces[enemy] {
-            bb |= piece
-                .all_coords()
-                .iter()
-                .map(|sqr| self.legal_moves(*sqr).unwrap())
-                .fold(BitBoard(0), |bb, attack| bb | attack);
+    /// Whether the current position has appeared three times since the last irreversible
+    /// move, a draw by the rules. The search stops at the first repeat instead
+    pub fn is_threefold(&self) -> bool {
+        self.history
+            .iter()
+            .rev()
+            .take(self.state.halfmove_clock as usize)
+            .skip(1)
+            .step_by(2)
+            .filter(|state| state.hash == self.state.hash)
+            .count()
+            >= 2
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub(crate) fn state_mut(&mut self) -> &mut State {
+        &mut self.state
+    }
+
+    /// The piece on a square and who it belongs to
+    pub fn piece_at(&self, sqr: Square) -> Option<(Color, Piece)> {
+        self.mailbox[sqr.index()]
+    }
+
+    /// The piece a move picks up or drops and who it belongs to
+    pub fn moving_piece(&self, mv: Move) -> Option<(Color, Piece)> {
+        match mv.dropped() {
+            Some(piece) => Some((self.turn, piece)),
+            None => self.piece_at(mv.from),
         }
+    }
 
-        println!("{}", bb);
+    /// Whether a move takes an enemy piece, including promotions that capture
+    pub fn is_capture(&self, mv: Move) -> bool {
+        // A Chess960 king can castle onto its own rook
+        mv.kind == LegalMove::EnPassant
+            || (mv.kind != LegalMove::Castle && self.piece_at(mv.to).is_some())
+    }
+
+    /// Function to determine wether a board is in check
+    pub fn in_check(&self, side: Color) -> bool {
+        if side == self.turn {
+            return !self.checkers().is_empty();
+        }
 
-        false
+        match self.piece_bb(side, Piece::King).lsb() {
+            Some(king) => self.is_square_attacked(king, !side),
+            None => false,
+        }
     }
 
-    pub fn legal_moves(&self, sqr: Square) -> Option<BitBoard> {
-        let piece_type = self.find_piece_type(sqr)?;
-        let side = self.find_side(sqr)?;
+    /// Enemy pieces attacking the king of the side to move
+    pub fn checkers(&self) -> BitBoard {
+        self.state.checkers
+    }
 
-        let bb = match piece_type {
-            Pieces::PAWN => self.legal_pawn_moves(sqr, side),
-            Pieces::BISHOP => self.legal_bishop_moves(sqr, side),
-            Pieces::KNIGHT => self.legal_knight_moves(Square::new(2, 3), side),
-            Pieces::ROOK => self.legal_rook_moves(sqr, side),
-            Pieces::QUEEN => self.legal_queen_moves(sqr, side),
-            Pieces::KING => self.legal_king_moves(Square::new(3, 4), side),
-            _ => todo!(),
+    // Recalculate the cached checkers, done whenever the side to move changes
+    pub(crate) fn update_checkers(&mut self) {
+        let side = self.turn;
+        let attackers = match self.piece_bb(side, Piece::King).lsb() {
+            Some(king) => self.attackers_to(king, !side),
+            None => BitBoard(0),
         };
+        self.state.checkers = self.variant.checkers(self, attackers);
+    }
 
-        println!("Yap {}", self.in_check(Sides::WHITE));
-        Some(bb)
+    /// Pieces of one colour attacking a square, found by looking outwards from the square
+    /// with each piece's attack pattern
+    pub fn attackers_to(&self, sqr: Square, color: Color) -> BitBoard {
+        self.attackers_to_occupied(sqr, color, self.occupied(color))
     }
 
-    // Generate a bitboard with all friendly pieces
-    fn friendly(&self, side: usize) -> BitBoard {
+    // Attackers with some other set of occupied squares, for seeing through pieces
+    pub(crate) fn attackers_to_occupied(
+        &self,
+        sqr: Square,
+        color: Color,
+        occupied: BitBoard,
+    ) -> BitBoard {
+        let queens = self.piece_bb(color, Piece::Queen);
+        let diagonal = self.piece_bb(color, Piece::Bishop) | queens;
+        let straight = self.piece_bb(color, Piece::Rook) | queens;
+
+        // A pawn of this colour attacks the square if the square attacks it as the other colour
+        (attacks::pawn_attacks(sqr, !color) & self.piece_bb(color, Piece::Pawn))
+            | (attacks::knight_attacks(sqr) & self.piece_bb(color, Piece::Knight))
+            | (attacks::king_attacks(sqr) & self.piece_bb(color, Piece::King))
+            | (attacks::bishop_attacks(sqr, occupied) & diagonal)
+            | (attacks::rook_attacks(sqr, occupied) & straight)
+    }
+
+    /// Whether any piece of a colour attacks a square
+    pub fn is_square_attacked(&self, sqr: Square, by: Color) -> bool {
+        !self.attackers_to(sqr, by).is_empty()
+    }
+
+    /// Generate a bitboard of every square attacked by a side
+    pub fn attacks(&self, side: Color) -> BitBoard {
         let mut bb = BitBoard(0);
 
-        for i in Pieces::all() {
-            bb |= self.pieces[side][i];
+        for piece in Piece::ALL {
+            for sqr in self.piece_bb(side, piece) {
+                bb |= match piece {
+                    Piece::Pawn => self.pawn_attacks(sqr, side),
+                    Piece::Bishop => self.legal_bishop_moves(sqr, side),
+                    Piece::Knight => self.legal_knight_moves(sqr, side),
+                    Piece::Rook => self.legal_rook_moves(sqr, side),
+                    Piece::Queen => self.legal_queen_moves(sqr, side),
+                    Piece::King => self.legal_king_moves(sqr, side),
+                };
+            }
         }
 
         bb
     }
 
-    // Generate a bitboard with the enemy pieces
-    fn enemy(&self, side: usize) -> BitBoard {
-        let mut bb = BitBoard(0);
-        let opposing = if side == Sides::WHITE {
-            Sides::BLACK
-        } else {
-            Sides::WHITE
+    pub fn legal_moves(&self, sqr: Square) -> Option<BitBoard> {
+        let (side, piece_type) = self.piece_at(sqr)?;
+
+        let bb = match piece_type {
+            Piece::Pawn => self.legal_pawn_moves(sqr, side),
+            Piece::Bishop => self.legal_bishop_moves(sqr, side),
+            Piece::Knight => self.legal_knight_moves(sqr, side),
+            Piece::Rook => self.legal_rook_moves(sqr, side),
+            Piece::Queen => self.legal_queen_moves(sqr, side),
+            Piece::King => self.legal_king_moves(sqr, side),
         };
 
-        for i in Pieces::all() {
-            bb |= self.pieces[opposing][i];
-        }
+        Some(bb)
+    }
 
-        bb
+    // Generate a bitboard with all friendly pieces
+    fn friendly(&self, side: Color) -> BitBoard {
+        self.color_bb(side)
     }
 
-    pub fn occupied(&self, side: usize) -> BitBoard {
-        self.enemy(side) | self.friendly(side)
+    // Generate a bitboard with the enemy pieces
+    fn enemy(&self, side: Color) -> BitBoard {
+        self.color_bb(!side)
     }
 
-    pub fn legal_pawn_moves(&self, sqr: Square, side: usize) -> BitBoard {
+    pub fn occupied(&self, side: Color) -> BitBoard {
+        self.friendly(side) | self.enemy(side)
+    }
+
+    pub fn legal_pawn_moves(&self, sqr: Square, side: Color) -> BitBoard {
         let mut bb = BitBoard(0);
-        let direction = if side == Sides::WHITE { 1 } else { -1 };
+        let direction = side.forward();
         let occupied = self.friendly(side) | self.enemy(side);
 
         // Check initial double movement
         let moved = match side {
-            Sides::WHITE => sqr.1 != 1,
-            Sides::BLACK => sqr.1 != 6,
-            _ => panic!("Side that doesnt exist"),
+            Color::White => sqr.rank() != 1,
+            Color::Black => sqr.rank() != 6,
         };
 
-        let single = BitBoard::from_square(Square::new(sqr.0, sqr.1 + 1));
-        let double = BitBoard::from_square(Square::new(sqr.0, sqr.1 + 2));
+        let Some(single) = sqr.offset(0, direction) else {
+            return bb;
+        };
 
         // Standard Movement
-        if !moved && ((single | double) & occupied).0 == 0 {
-            bb |= single | double;
-        } else if (single & occupied).0 == 0 {
-            bb |= single;
+        if (BitBoard::from_square(single) & occupied).0 == 0 {
+            bb |= BitBoard::from_square(single);
+
+            if let (false, Some(double)) = (moved, single.offset(0, direction)) {
+                bb |= BitBoard::from_square(double) & !occupied;
+            }
+        }
+
+        // Attacking, the en passant square counts as an enemy piece
+        let mut targets = self.enemy(side);
+        if let Some(ep) = self.state.en_passant {
+            targets |= BitBoard::from_square(ep);
+        }
+        bb |= self.pawn_attacks(sqr, side) & targets;
+
+        bb
+    }
+
+    /// Squares a pawn attacks whether or not there is anything on them
+    pub fn pawn_attacks(&self, sqr: Square, side: Color) -> BitBoard {
+        attacks::pawn_attacks(sqr, side)
+    }
+
+    pub fn legal_bishop_moves(&self, sqr: Square, side: Color) -> BitBoard {
+        attacks::bishop_attacks(sqr, self.occupied(side)) & !self.friendly(side)
+    }
+
+    pub fn legal_knight_moves(&self, sqr: Square, side: Color) -> BitBoard {
+        attacks::knight_attacks(sqr) & !self.friendly(side)
+    }
+
+    pub fn legal_rook_moves(&self, sqr: Square, side: Color) -> BitBoard {
+        attacks::rook_attacks(sqr, self.occupied(side)) & !self.friendly(side)
+    }
+
+    pub fn legal_queen_moves(&self, sqr: Square, side: Color) -> BitBoard {
+        attacks::queen_attacks(sqr, self.occupied(side)) & !self.friendly(side)
+    }
+
+    pub fn legal_king_moves(&self, sqr: Square, side: Color) -> BitBoard {
+        attacks::king_attacks(sqr) & !self.friendly(side)
+    }
+
+    /// Generate every legal move for the side to move
+    pub fn generate_moves(&mut self) -> MoveList {
+        let mut moves = MoveList::new();
+        if self.winner().is_some() {
+            return moves;
+        }
+        if !self.variant.standard_legality() {
+            self.variant_moves(&mut moves);
+            return moves;
+        }
+
+        let side = self.turn;
+        let king = self.piece_bb(side, Piece::King).lsb();
+        let checkers = self.checkers();
+
+        if let Some(king) = king {
+            // The king can't hide behind itself from a slider so take it off the board
+            let occupied = self.occupied(side) ^ BitBoard::from_square(king);
+            let targets = self.legal_king_moves(king, side);
+
+            for to in targets {
+                if self.attackers_to_occupied(to, !side, occupied).is_empty() {
+                    self.push_moves(Piece::King, king, BitBoard::from_square(to), &mut moves);
+                }
+            }
+        }
+
+        // Only the king can get out of double check
+        if checkers.count() > 1 {
+            return moves;
         }
 
-        let y = match sqr.1.checked_add_signed(direction) {
-            Some(n) if n <= 7 => n,
-            _ => panic!("Not bad"),
+        // In check everything else has to take the checker or block it
+        let evasions = match (king, checkers.lsb()) {
+            (Some(king), Some(checker)) => {
+                BitBoard::from_square(checker) | attacks::between(king, checker)
+            }
+            _ => BitBoard(!0),
         };
+        let pinned = self.pinned(side);
+        let pawns = self.piece_bb(side, Piece::Pawn);
+        self.pawn_moves(pawns & !pinned, evasions, &mut moves);
 
-        // Attacking
-        for i in [1, -1] {
-            let x = match sqr.0.checked_add_signed(i) {
-                Some(n) if n <= 7 => n,
-                _ => continue,
+        for piece in Piece::ALL.into_iter().filter(|&p| p != Piece::King) {
+            // Free pawns were all done at once above
+            let pieces = match piece {
+                Piece::Pawn => pawns & pinned,
+                _ => self.piece_bb(side, piece),
+            };
+            let skip = match (piece, self.state.en_passant) {
+                (Piece::Pawn, Some(ep)) => BitBoard::from_square(ep),
+                _ => BitBoard(0),
             };
 
-            let mask = BitBoard::from_square(Square::new(x, y));
-            bb |= mask & self.enemy(side);
+            for from in pieces {
+                let mut targets = self.legal_moves(from).unwrap();
+
+                // Pinned pieces can only slide along the pin
+                if let (Some(king), true) =
+                    (king, !(pinned & BitBoard::from_square(from)).is_empty())
+                {
+                    targets &= attacks::line(king, from);
+                }
+
+                self.push_moves(piece, from, targets & evasions & !skip, &mut moves);
+            }
         }
-        bb
+
+        // Dropping can only get out of check by blocking
+        self.drop_moves(evasions, &mut moves);
+
+        // En passant removes two pieces from a rank so pins are hard to spot, just try it
+        if let Some(ep) = self.state.en_passant {
+            for from in attacks::pawn_attacks(ep, !side) & pawns {
+                let mv = Move::new(from, ep, LegalMove::EnPassant);
+                self.make_move(mv);
+                if !self.in_check(side) {
+                    moves.push(mv);
+                }
+                self.unmake_move(mv);
+            }
+        }
+
+        if checkers.is_empty() {
+            self.castling_moves(&mut moves);
+        }
+
+        moves
     }
 
-    pub fn legal_bishop_moves(&self, sqr: Square, side: usize) -> BitBoard {
-        self.generate_sliding_moves(sqr, side, &BISHOP_OFFSETS)
+    // Every move that would be legal if kings didn't matter, kept if the variant is happy with
+    // the position after it
+    fn variant_moves(&mut self, moves: &mut MoveList) {
+        let side = self.turn;
+        let mut candidates = MoveList::new();
+        let pawns = self.piece_bb(side, Piece::Pawn);
+
+        self.pawn_moves(pawns, BitBoard(!0), &mut candidates);
+        for piece in Piece::ALL.into_iter().filter(|&p| p != Piece::Pawn) {
+            for from in self.piece_bb(side, piece) {
+                let targets = self.legal_moves(from).unwrap();
+                self.push_moves(piece, from, targets, &mut candidates);
+            }
+        }
+        if let Some(ep) = self.state.en_passant {
+            for from in attacks::pawn_attacks(ep, !side) & pawns {
+                candidates.push(Move::new(from, ep, LegalMove::EnPassant));
+            }
+        }
+        if self.checkers().is_empty() {
+            self.castling_moves(&mut candidates);
+        }
+        self.drop_moves(BitBoard(!0), &mut candidates);
+
+        let variant = self.variant;
+        for mv in candidates.iter().copied() {
+            self.make_move(mv);
+            if variant.is_legal(self, side) {
+                moves.push(mv);
+            }
+            self.unmake_move(mv);
+        }
     }
 
-    pub fn legal_knight_moves(&self, sqr: Square, side: usize) -> BitBoard {
-        let mut bb = BitBoard(0);
-        for (dx, dy) in KNIGHT_OFFSETS {
-            let x = match sqr.0.checked_add_signed(dx) {
-                Some(n) if n <= 7 => n,
-                _ => continue,
-            };
-            let y = match sqr.1.checked_add_signed(dy) {
-                Some(n) if n <= 7 => n,
-                _ => continue,
+    /// Pieces of a side that can't leave the line between their king and an enemy slider
+    pub fn pinned(&self, side: Color) -> BitBoard {
+        let Some(king) = self.piece_bb(side, Piece::King).lsb() else {
+            return BitBoard(0);
+        };
+
+        let enemy = !side;
+        let queens = self.piece_bb(enemy, Piece::Queen);
+        // Sliders that would attack the king if none of our pieces were in the way
+        let snipers = (attacks::rook_attacks(king, self.enemy(side))
+            & (self.piece_bb(enemy, Piece::Rook) | queens))
+            | (attacks::bishop_attacks(king, self.enemy(side))
+                & (self.piece_bb(enemy, Piece::Bishop) | queens));
+
+        let mut pinned = BitBoard(0);
+        for sniper in snipers {
+            let blockers = attacks::between(king, sniper) & self.occupied(side);
+            if blockers.count() == 1 {
+                pinned |= blockers & self.friendly(side);
+            }
+        }
+
+        pinned
+    }
+
+    // Pushes and captures for a set of pawns, worked out for all of them at once
+    fn pawn_moves(&self, pawns: BitBoard, evasions: BitBoard, moves: &mut MoveList) {
+        let side = self.turn;
+        let back = -side.forward();
+        let empty = !self.occupied(side);
+        let enemy = self.enemy(side);
+
+        let single = attacks::pawn_pushes(pawns, empty, side) & evasions;
+        let double = attacks::pawn_double_pushes(pawns, empty, side) & evasions;
+        let (east, west) = attacks::pawn_captures(pawns, side);
+
+        // Work back from each target to the pawn that got there
+        for (targets, files, ranks) in [
+            (single, 0, back),
+            (double, 0, 2 * back),
+            (east & enemy & evasions, -1, back),
+            (west & enemy & evasions, 1, back),
+        ] {
+            for to in targets {
+                let from = to.offset(files, ranks).unwrap();
+                self.push_moves(Piece::Pawn, from, BitBoard::from_square(to), moves);
+            }
+        }
+    }
+
+    // Turn the target squares of a piece into moves, splitting out promotions and captures
+    fn push_moves(&self, piece: Piece, from: Square, targets: BitBoard, moves: &mut MoveList) {
+        let side = self.turn;
+        let enemy = self.enemy(side);
+
+        for to in targets {
+            let capture = (BitBoard::from_square(to) & enemy).0 != 0;
+
+            if piece == Piece::Pawn && to.rank() == (!side).back_rank() {
+                for promotion in Piece::PROMOTIONS {
+                    moves.push(Move {
+                        from,
+                        to,
+                        kind: LegalMove::Promotion,
+                        promotion: Some(promotion),
+                    });
+                }
+            } else if capture {
+                moves.push(Move::new(from, to, LegalMove::Attack));
+            } else {
+                moves.push(Move::new(from, to, LegalMove::Move));
+            }
+        }
+    }
+
+    // Pieces from the pocket put down on any of the empty target squares, pawns can't go on
+    // the first or last rank
+    fn drop_moves(&self, targets: BitBoard, moves: &mut MoveList) {
+        let side = self.turn;
+        let empty = targets & !self.occupied(side);
+
+        for piece in Piece::POCKET {
+            if self.pocket(side, piece) == 0 {
+                continue;
+            }
+
+            let squares = match piece {
+                Piece::Pawn => empty & !BACK_RANKS,
+                _ => empty,
             };
+            for to in squares {
+                moves.push(Move::drop(piece, to));
+            }
+        }
+    }
 
-            let mv = BitBoard::from_square(Square::new(x, y));
-            bb |= mv;
+    fn castling_moves(&self, moves: &mut MoveList) {
+        let side = self.turn;
+        let rights = self.state.castling_rights;
+        let occupied = self.occupied(side);
+
+        if !rights.has(CastlingRights::kingside(side))
+            && !rights.has(CastlingRights::queenside(side))
+        {
+            return;
         }
+        let Some(king) = self.piece_bb(side, Piece::King).lsb() else {
+            return;
+        };
 
-        bb &= !self.friendly(side);
+        for (right, kingside) in [
+            (CastlingRights::kingside(side), true),
+            (CastlingRights::queenside(side), false),
+        ] {
+            if !rights.has(right) {
+                continue;
+            }
 
-        bb
+            let to = Self::castling_target(side, kingside);
+            let (rook, rook_to) = self.castling_rook(side, to);
+            if self.piece_at(rook) != Some((side, Piece::Rook)) {
+                continue;
+            }
+
+            // Everything the king and rook cross has to be empty apart from the two of them,
+            // which in Chess960 can be in each other's way
+            let path = attacks::between(king, to)
+                | BitBoard::from_square(to)
+                | attacks::between(rook, rook_to)
+                | BitBoard::from_square(rook_to);
+            let others = occupied ^ BitBoard::from_square(king) ^ BitBoard::from_square(rook);
+            if !(path & others).is_empty() {
+                continue;
+            }
+
+            // The king can't cross an attacked square. The rook is taken off first as it
+            // could be shielding the king's new square from a slider along the rank
+            let crossed = attacks::between(king, to)
+                | BitBoard::from_square(king)
+                | BitBoard::from_square(to);
+            let without_rook = occupied ^ BitBoard::from_square(rook);
+            let attacked = crossed.into_iter().any(|sqr| {
+                !self
+                    .attackers_to_occupied(sqr, !side, without_rook)
+                    .is_empty()
+            });
+
+            if !attacked {
+                moves.push(Move::new(king, to, LegalMove::Castle));
+            }
+        }
     }
 
-    pub fn legal_rook_moves(&self, sqr: Square, side: usize) -> BitBoard {
-        self.generate_sliding_moves(sqr, side, &ROOK_OFFSETS)
+    // Where the king goes when castling, g1 or c1 whatever file it starts on
+    fn castling_target(side: Color, kingside: bool) -> Square {
+        let file = if kingside { 6 } else { 2 };
+        Square::new(file, side.back_rank()).unwrap()
     }
 
-    pub fn legal_queen_moves(&self, sqr: Square, side: usize) -> BitBoard {
-        self.generate_sliding_moves(sqr, side, &BISHOP_OFFSETS)
-            | self.generate_sliding_moves(sqr, side, &ROOK_OFFSETS)
+    // Add or remove a piece on a square
+    pub(crate) fn toggle_piece(&mut self, side: Color, piece: Piece, sqr: Square) {
+        let bb = BitBoard::from_square(sqr);
+        self.pieces[side as usize][piece as usize] ^= bb;
+        self.sides[side as usize] ^= bb;
+        self.state.hash ^= KEYS.piece(side, piece, sqr);
+
+        let slot = &mut self.mailbox[sqr.index()];
+        *slot = match *slot {
+            Some(_) => None,
+            None => Some((side, piece)),
+        };
     }
 
-    pub fn legal_king_moves(&self, sqr: Square, side: usize) -> BitBoard {
-        let mut bb = BitBoard(0);
+    // Squares the rook moves between when castling to the given king square
+    fn castling_rook(&self, side: Color, to: Square) -> (Square, Square) {
+        let kingside = to.file() == 6;
+        let rank = side.back_rank();
+        let from = Square::new(self.castling_file(side, kingside), rank).unwrap();
+        let to = Square::new(if kingside { 5 } else { 3 }, rank).unwrap();
+        (from, to)
+    }
 
-        let mut x: u8 = 0;
-        let mut y: u8 = 0;
+    /// Take a piece off the board for the rest of the move being made, for use in
+    /// Variant::after_move. unmake_move puts it back
+    pub fn remove_piece(&mut self, sqr: Square) {
+        let Some((side, piece)) = self.piece_at(sqr) else {
+            return;
+        };
 
-        for (dx, dy) in KING_OFFSETS {
-            x = match sqr.0.checked_add_signed(dx) {
-                Some(n) if n <= 7 => n,
-                _ => break,
-            };
+        self.toggle_piece(side, piece, sqr);
+        self.state.promoted &= !BitBoard::from_square(sqr);
+        self.removed.push((side, piece, sqr));
+        self.state.removed += 1;
+
+        if piece == Piece::King {
+            let rights = &mut self.state.castling_rights;
+            rights.remove(CastlingRights::kingside(side));
+            rights.remove(CastlingRights::queenside(side));
+        }
+        self.lose_castling_rights(sqr);
+    }
+
+    // A rook leaving its square or being taken loses the right that goes with it
+    fn lose_castling_rights(&mut self, sqr: Square) {
+        for color in Color::ALL {
+            if sqr.rank() != color.back_rank() {
+                continue;
+            }
+
+            for kingside in [true, false] {
+                if sqr.file() == self.castling_file(color, kingside) {
+                    self.state.castling_rights.remove(if kingside {
+                        CastlingRights::kingside(color)
+                    } else {
+                        CastlingRights::queenside(color)
+                    });
+                }
+            }
+        }
+    }
+
+    // Square of the pawn taken by an en passant capture
+    fn en_passant_victim(mv: Move) -> Square {
+        Square::new(mv.to.file(), mv.from.rank()).unwrap()
+    }
+
+    /// Play a move, it is expected to be one returned from generate_moves
+    pub fn make_move(&mut self, mv: Move) {
+        let side = self.turn;
+        let enemy = !side;
+        let (_, piece) = self
+            .moving_piece(mv)
+            .expect("No piece on the square being moved from");
+
+        self.history.push(self.state);
+        self.state.hash ^= KEYS.castling(self.state.castling_rights.bits())
+            ^ KEYS.en_passant(self.state.en_passant)
+            ^ KEYS.side();
+        self.state.en_passant = None;
+        self.state.halfmove_clock += 1;
+        self.state.captured = None;
+        self.state.removed = 0;
+
+        // Remove the captured piece
+        if mv.kind == LegalMove::EnPassant {
+            self.toggle_piece(enemy, Piece::Pawn, Self::en_passant_victim(mv));
+            self.state.captured = Some(Piece::Pawn);
+        } else if let Some((_, captured)) = self
+            .piece_at(mv.to)
+            .filter(|_| mv.kind != LegalMove::Castle)
+        {
+            self.toggle_piece(enemy, captured, mv.to);
+            self.state.captured = Some(captured);
+        }
+
+        // Promoted pieces are followed around the board for when they get captured
+        let (from, to) = (BitBoard::from_square(mv.from), BitBoard::from_square(mv.to));
+        let mut promoted = self.state.promoted;
+        self.state.captured_promoted = !(promoted & to).is_empty();
+        promoted &= !to;
+        if !(promoted & from).is_empty() {
+            promoted ^= from | to;
+        }
+        if mv.kind == LegalMove::Promotion {
+            promoted |= to;
+        }
+        self.state.promoted = promoted;
+
+        if mv.kind == LegalMove::Castle {
+            // Both come off before either goes back as they can land on each other's squares
+            let (from, to) = self.castling_rook(side, mv.to);
+            self.toggle_piece(side, Piece::Rook, from);
+            self.toggle_piece(side, Piece::King, mv.from);
+            self.toggle_piece(side, Piece::King, mv.to);
+            self.toggle_piece(side, Piece::Rook, to);
+        } else if mv.dropped().is_some() {
+            self.take_from_pocket(side, piece);
+            self.toggle_piece(side, piece, mv.to);
+        } else {
+            self.toggle_piece(side, piece, mv.from);
+            self.toggle_piece(side, mv.promotion.unwrap_or(piece), mv.to);
+        }
+
+        if piece == Piece::Pawn {
+            self.state.halfmove_clock = 0;
+
+            if mv.from.rank().abs_diff(mv.to.rank()) == 2 {
+                self.state.en_passant = mv.from.offset(0, side.forward());
+            }
+        }
+
+        if self.state.captured.is_some() {
+            self.state.halfmove_clock = 0;
+        }
+
+        // Moving the king or a rook, or having a rook captured loses castling rights
+        if piece == Piece::King {
+            let rights = &mut self.state.castling_rights;
+            rights.remove(CastlingRights::kingside(side));
+            rights.remove(CastlingRights::queenside(side));
+        }
+
+        for sqr in [mv.from, mv.to] {
+            self.lose_castling_rights(sqr);
+        }
+
+        if side == Color::Black {
+            self.state.fullmove_number += 1;
+        }
+
+        let variant = self.variant;
+        variant.after_move(self, mv);
 
-            y = match sqr.1.checked_add_signed(dy) {
-                Some(n) if n <= 7 => n,
-                _ => break,
+        self.state.hash ^= KEYS.castling(self.state.castling_rights.bits())
+            ^ KEYS.en_passant(self.state.en_passant);
+        self.turn = enemy;
+        self.update_checkers();
+    }
+
+    /// Take back a move, must be the last one made
+    pub fn unmake_move(&mut self, mv: Move) {
+        self.turn = !self.turn;
+        let side = self.turn;
+        let enemy = !side;
+
+        // Anything the variant removed goes back first so the move's own pieces are there
+        for _ in 0..self.state.removed {
+            let (color, piece, sqr) = self.removed.pop().expect("Removed pieces are recorded");
+            self.toggle_piece(color, piece, sqr);
+        }
+
+        let (_, moved) = self
+            .piece_at(mv.to)
+            .expect("No piece on the square being moved to");
+        let piece = if mv.promotion.is_some() {
+            Piece::Pawn
+        } else {
+            moved
+        };
+
+        if mv.kind == LegalMove::Castle {
+            let (from, to) = self.castling_rook(side, mv.to);
+            self.toggle_piece(side, Piece::Rook, to);
+            self.toggle_piece(side, Piece::King, mv.to);
+            self.toggle_piece(side, Piece::King, mv.from);
+            self.toggle_piece(side, Piece::Rook, from);
+        } else if mv.dropped().is_some() {
+            self.toggle_piece(side, moved, mv.to);
+        } else {
+            self.toggle_piece(side, moved, mv.to);
+            self.toggle_piece(side, piece, mv.from);
+        }
+
+        if let Some(captured) = self.state.captured {
+            let sqr = if mv.kind == LegalMove::EnPassant {
+                Self::en_passant_victim(mv)
+            } else {
+                mv.to
             };
+            self.toggle_piece(enemy, captured, sqr);
+        }
 
-            let mv = BitBoard::from_square(Square::new(x, y));
-            bb |= mv & !self.friendly(side);
+        self.state = self
+            .history
+            .pop()
+            .expect("Tried to unmake a move that was never made");
+    }
+
+    /// Legal move matching coordinate notation e.g. e2e4 or e7e8q
+    pub fn find_move(&mut self, text: &str) -> Option<Move> {
+        self.generate_moves()
+            .into_iter()
+            .find(|&mv| self.coordinates(mv) == text)
+    }
+
+    /// Coordinate notation for a move in this game. Chess960 castling is written as the king
+    /// taking its own rook, a king going to g1 could otherwise be castling or just a king move
+    pub fn coordinates(&self, mv: Move) -> String {
+        if self.chess960 && mv.kind == LegalMove::Castle {
+            let side = if mv.from.rank() == 0 {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let (rook, _) = self.castling_rook(side, mv.to);
+            return format!("{}{rook}", mv.from);
         }
+        mv.to_string()
+    }
 
-        bb
+    /// Pass the turn without moving, used by null move pruning. Not valid in check
+    pub fn make_null_move(&mut self) {
+        self.history.push(self.state);
+        self.state.hash ^= KEYS.en_passant(self.state.en_passant) ^ KEYS.side();
+        self.state.en_passant = None;
+        self.state.halfmove_clock += 1;
+        self.state.captured = None;
+        self.turn = !self.turn;
+        self.update_checkers();
     }
 
-    fn generate_sliding_moves(
-        &self,
-        sqr: Square,
-        side: usize,
-        directions: &[(i8, i8)],
-    ) -> BitBoard {
-        let occupied = self.occupied(side);
-        let mut bb = BitBoard(0);
+    pub fn unmake_null_move(&mut self) {
+        self.turn = !self.turn;
+        self.state = self
+            .history
+            .pop()
+            .expect("Tried to unmake a move that was never made");
+    }
 
-        for (dx, dy) in directions {
-            let mut x = sqr.0;
-            let mut y = sqr.1;
+    /// Count the leaf nodes of the move tree to a given depth
+    pub fn perft(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
 
-            loop {
-                x = match x.checked_add_signed(*dx) {
-                    Some(n) if n <= 7 => n,
-                    _ => break,
-                };
+        let moves = self.generate_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
 
-                y = match y.checked_add_signed(*dy) {
-                    Some(n) if n <= 7 => n,
-                    _ => break,
-                };
+        let mut nodes = 0;
+        for mv in moves {
+            self.make_move(mv);
+            nodes += self.perft(depth - 1);
+            self.unmake_move(mv);
+        }
+        nodes
+    }
+}
 
-                let mv = BitBoard::from_square(Square::new(x, y));
-                if (mv & self.enemy(side)).0 > 0 {
-                    bb |= mv;
-                    break;
-                } else if (mv & occupied).0 > 0 {
-                    break;
-                }
-                bb |= mv;
+/// Board from white's side with Unicode pieces, the alternate form `{:#}` uses FEN letters
+/// for terminals without chess symbols. Pockets go underneath in FEN letters
+impl Display for Game {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for rank in (0..8).rev() {
+            write!(f, "{} ", rank + 1)?;
+            for file in 0..8 {
+                let chr = match self.piece_at(Square::new(file, rank).unwrap()) {
+                    Some((side, piece)) if f.alternate() => match side {
+                        Color::White => piece.to_char().to_ascii_uppercase(),
+                        Color::Black => piece.to_char(),
+                    },
+                    Some((side, piece)) => piece.to_unicode(side),
+                    None => '.',
+                };
+                write!(f, " {chr}")?;
             }
+            writeln!(f)?;
         }
-        bb
+        write!(f, "   a b c d e f g h")?;
+
+        if self.has_pockets() {
+            write!(f, "\n   [{}]", self.pocket_letters())?;
+        }
+        Ok(())
     }
 }
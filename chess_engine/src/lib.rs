@@ -1,23 +1,1108 @@
 #![allow(dead_code)]
 
-mod board;
-mod game;
-mod state;
+pub mod attacks;
+pub mod board;
+pub mod book;
+pub mod editor;
+pub mod eval;
+pub mod fen;
+pub mod game;
+pub mod movelist;
+pub mod ordering;
+pub mod pgn;
+pub mod piece;
+pub mod san;
+pub mod search;
+pub mod see;
+pub mod square;
+pub mod state;
+pub mod tablebase;
+pub mod tt;
+pub mod validate;
+pub mod variant;
+pub mod zobrist;
 
+#[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use crate::{
-        board::BitBoard,
-        game::{Game, Pieces, Sides, Square},
+        attacks::{
+            bishop_attacks, pawn_attacks_set, pawn_double_pushes, rook_attacks,
+            sliding_attacks_slow,
+        },
+        board::{BitBoard, FILE_A, FILE_H},
+        book::{encode_move, polyglot_key, Book, BookBuilder},
+        eval::evaluate,
+        fen::{FenError, START_FEN},
+        game::{chess960_back_rank, Game, LegalMove, Move, STANDARD_POSITION},
+        movelist::{MoveList, MAX_MOVES},
+        ordering::MoveOrderer,
+        pgn::{read_games, Pgn, PgnError},
+        piece::{Color, Piece},
+        search::{mate_in, Limits, Search, SearchConfig, MATE},
+        square::Square,
+        tablebase::{Material, Outcome, Tablebase, TablebaseError},
+        tt::{Bound, TranspositionTable},
+        validate::PositionError,
+        variant::{self, ATOMIC, BUGHOUSE, CRAZYHOUSE, KING_OF_THE_HILL, THREE_CHECK},
     };
-    use std::fmt::Display;
 
     #[test]
     fn it_works() {
         let mut game = Game::new();
-        game.init();
+        game.init(STANDARD_POSITION);
 
-        let x = game.legal_moves(Square::new(4, 0));
+        let x = game.legal_moves(Square::E1);
+        assert!(x.is_some());
     }
-}
 
-// R N B Q K B N R
+    #[test]
+    fn perft_start() {
+        let mut game = Game::from_fen(START_FEN).unwrap();
+
+        assert_eq!(game.perft(1), 20);
+        assert_eq!(game.perft(2), 400);
+        assert_eq!(game.perft(3), 8902);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        let mut game =
+            Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+
+        assert_eq!(game.perft(1), 48);
+        assert_eq!(game.perft(2), 2039);
+        assert_eq!(game.perft(3), 97862);
+    }
+
+    #[test]
+    fn perft_chess960() {
+        // From the Chess960 perft suite, castling with the king and rook next to each other
+        for (fen, nodes) in [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                12189,
+            ),
+            (
+                "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+                18002,
+            ),
+            (
+                "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+                10471,
+            ),
+        ] {
+            let mut game = Game::from_fen(fen).unwrap();
+            assert!(game.is_chess960());
+            assert_eq!(game.perft(3), nodes, "{fen}");
+        }
+    }
+
+    #[test]
+    fn chess960() {
+        let mut game = Game::new();
+        game.init(STANDARD_POSITION);
+        assert_eq!(game.to_fen(), START_FEN);
+        assert!(!game.is_chess960());
+        let mut game = Game::new();
+        game.init(STANDARD_POSITION + 960);
+        assert_eq!(game.to_fen(), START_FEN);
+        assert!(!game.is_chess960());
+
+        let mut game = Game::new();
+        game.init(0);
+        assert_eq!(
+            game.to_fen(),
+            "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1"
+        );
+        assert_eq!(game.perft(2), 400);
+
+        // Bishops on opposite colours and the king between the rooks every time
+        for number in 0..960 {
+            let rank = chess960_back_rank(number);
+            let files = |piece| (0..8).filter(move |&f| rank[f] == piece);
+            let bishops: Vec<usize> = files(Piece::Bishop).collect();
+            let rooks: Vec<usize> = files(Piece::Rook).collect();
+            let king = files(Piece::King).next().unwrap();
+            assert_ne!(bishops[0] % 2, bishops[1] % 2);
+            assert!(rooks[0] < king && king < rooks[1]);
+        }
+
+        // Castling either way, the king crosses its rook going one way and lands on it the other
+        let fen = "rk5r/8/8/8/8/8/8/RK5R w HAha - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        assert_eq!(game.to_fen(), "rk5r/8/8/8/8/8/8/RK5R w KQkq - 0 1");
+        for (text, after) in [
+            ("b1a1", "rk5r/8/8/8/8/8/8/2KR3R b kq - 1 1"),
+            ("b1h1", "rk5r/8/8/8/8/8/8/R4RK1 b kq - 1 1"),
+        ] {
+            let mv = game.find_move(text).unwrap();
+            assert!(!game.is_capture(mv));
+            game.make_move(mv);
+            assert_eq!(game.to_fen(), after);
+            assert_eq!(game.hash(), Game::from_fen(after).unwrap().hash());
+            game.unmake_move(mv);
+            assert_eq!(game.to_fen(), "rk5r/8/8/8/8/8/8/RK5R w KQkq - 0 1");
+        }
+        let mv = game.find_move("b1h1").unwrap();
+        assert_eq!(game.to_san(mv), "O-O");
+
+        // A rook further out than the castling one needs the file written
+        let fen = "4k3/8/8/8/8/8/8/R2K1R1R w F - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        assert_eq!(game.to_fen(), fen);
+        let mv = game.find_move("d1f1").unwrap();
+        game.make_move(mv);
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/R4RKR b - - 1 1");
+
+        assert_eq!(
+            Game::from_fen("4k3/8/8/8/8/8/8/R3K2R w E - 0 1").err(),
+            Some(FenError::InvalidCastling)
+        );
+    }
+
+    #[test]
+    fn perft_tricky() {
+        // En passant pins, promotions and checks from the standard perft suite
+        for (fen, depth, nodes) in [
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4, 43238),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                3,
+                9467,
+            ),
+            (
+                "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                3,
+                62379,
+            ),
+        ] {
+            let mut game = Game::from_fen(fen).unwrap();
+            assert_eq!(game.perft(depth), nodes, "{fen}");
+        }
+    }
+
+    #[test]
+    fn magic_attacks() {
+        // Compare the tables against walking the rays for a spread of occupancies
+        let mut occupied: u64 = 0x0123_4567_89AB_CDEF;
+        for _ in 0..200 {
+            occupied ^= occupied << 13;
+            occupied ^= occupied >> 7;
+            occupied ^= occupied << 17;
+
+            for index in 0..64 {
+                let sqr = Square::ALL[index];
+                let bb = BitBoard(occupied & occupied.rotate_left(index as u32));
+
+                assert_eq!(
+                    rook_attacks(sqr, bb).0,
+                    sliding_attacks_slow(sqr, bb, true).0
+                );
+                assert_eq!(
+                    bishop_attacks(sqr, bb).0,
+                    sliding_attacks_slow(sqr, bb, false).0
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn search_finds_mate() {
+        let mut game =
+            Game::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4")
+                .unwrap();
+
+        let result = Search::new().search(&mut game, Limits::depth(2));
+        assert_eq!(result.best_move.unwrap().to_string(), "h5f7");
+        assert_eq!(result.score, MATE - 1);
+    }
+
+    #[test]
+    fn eval_symmetry() {
+        let game = Game::from_fen(START_FEN).unwrap();
+        assert_eq!(evaluate(&game), 0);
+
+        // Same position with colours swapped should score the same for the side to move
+        let white = Game::from_fen("4k3/pp3ppp/8/3n4/8/2N5/PPP2PPP/4K2R w K - 0 1").unwrap();
+        let black = Game::from_fen("4k2r/ppp2ppp/2n5/8/3N4/8/PP3PPP/4K3 b k - 0 1").unwrap();
+        assert_eq!(evaluate(&white), evaluate(&black));
+    }
+
+    #[test]
+    fn eval_material() {
+        // White is a queen up
+        let white = Game::from_fen("3qk3/8/8/8/8/8/8/3QKQ2 w - - 0 1").unwrap();
+        let black = Game::from_fen("3qk3/8/8/8/8/8/8/3QKQ2 b - - 0 1").unwrap();
+
+        assert!(evaluate(&white) > 500);
+        assert_eq!(evaluate(&white), -evaluate(&black));
+    }
+
+    fn assert_mailbox_matches(game: &Game) {
+        for sqr in Square::ALL {
+            let expected = Color::ALL.into_iter().find_map(|color| {
+                Piece::ALL
+                    .into_iter()
+                    .find(|&piece| {
+                        (game.piece_bb(color, piece) & BitBoard::from_square(sqr)).0 != 0
+                    })
+                    .map(|piece| (color, piece))
+            });
+            assert_eq!(game.piece_at(sqr), expected, "{sqr}");
+        }
+    }
+
+    #[test]
+    fn hash_incremental() {
+        let mut game =
+            Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let start = game.hash();
+
+        for mv in game.generate_moves() {
+            game.make_move(mv);
+            assert_eq!(game.hash(), game.compute_hash());
+
+            for reply in game.generate_moves() {
+                game.make_move(reply);
+                assert_eq!(game.hash(), game.compute_hash());
+                assert_mailbox_matches(&game);
+                game.unmake_move(reply);
+            }
+
+            game.unmake_move(mv);
+            assert_eq!(game.hash(), start);
+            assert_mailbox_matches(&game);
+        }
+    }
+
+    #[test]
+    fn repetition() {
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        let start = game.hash();
+
+        for round in 0..2 {
+            for mv in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+                let mv = game
+                    .generate_moves()
+                    .into_iter()
+                    .find(|m| m.to_string() == mv)
+                    .unwrap();
+                game.make_move(mv);
+            }
+
+            // The search calls the first repeat a draw, the rules need a third occurrence
+            assert_eq!(game.hash(), start);
+            assert!(game.is_repetition());
+            assert_eq!(game.is_threefold(), round == 1);
+        }
+    }
+
+    #[test]
+    fn tt_replace_by_depth() {
+        let tt = TranspositionTable::new(1);
+        tt.store(42, 5, 10, Bound::Exact, None);
+        tt.store(42, 3, 20, Bound::Lower, None);
+        assert_eq!(tt.probe(42).unwrap().score, 10);
+
+        // Entries from an older search are always replaced
+        tt.new_search();
+        tt.store(42, 3, 20, Bound::Lower, None);
+        assert_eq!(tt.probe(42).unwrap().depth, 3);
+        assert!(tt.probe(43).is_none());
+
+        tt.clear();
+        assert!(tt.probe(42).is_none());
+    }
+
+    #[test]
+    fn search_mate_in_two() {
+        let mut game = Game::from_fen("7k/8/8/8/8/8/R7/1R5K w - - 0 1").unwrap();
+        let mut search = Search::new();
+
+        let result = search.search(&mut game, Limits::depth(4));
+        assert_eq!(result.score, MATE - 3);
+        assert_eq!(result.pv.len(), 3);
+    }
+
+    #[test]
+    fn square_notation() {
+        let e4: Square = "e4".parse().unwrap();
+        assert_eq!((e4.file(), e4.rank()), (4, 3));
+        assert_eq!(e4.to_string(), "e4");
+        assert_eq!(Square::new(4, 3), Some(e4));
+
+        assert!("i1".parse::<Square>().is_err());
+        assert!("a9".parse::<Square>().is_err());
+        assert!("e44".parse::<Square>().is_err());
+        assert_eq!(Square::new(8, 0), None);
+        assert_eq!(Square::from_index(64), None);
+        assert_eq!(Square::H8.offset(1, 0), None);
+        assert_eq!(Square::A1.offset(-1, 0), None);
+    }
+
+    #[test]
+    fn pieces_and_colors() {
+        let game = Game::from_fen(START_FEN).unwrap();
+
+        assert_eq!(game.turn(), Color::White);
+        assert_eq!(game.piece_at(Square::E1), Some((Color::White, Piece::King)));
+        assert_eq!(
+            game.piece_at(Square::D8),
+            Some((Color::Black, Piece::Queen))
+        );
+        assert_eq!(game.piece_at("e4".parse().unwrap()), None);
+        assert_eq!(game.piece_bb(Color::Black, Piece::Knight).0.count_ones(), 2);
+        assert_eq!(Piece::from_char('Q'), Some(Piece::Queen));
+        assert_eq!(!Color::White, Color::Black);
+    }
+
+    #[test]
+    fn attack_queries() {
+        let game = Game::from_fen("4k3/8/8/3q4/8/2N5/1P6/4K2R b K - 0 1").unwrap();
+        let d5: Square = "d5".parse().unwrap();
+
+        // The pawn on b2 is too far away, only the knight reaches d5
+        let attackers = game.attackers_to(d5, Color::White);
+        assert_eq!(attackers.all_coords(), vec!["c3".parse().unwrap()]);
+        assert!(game.is_square_attacked("a2".parse().unwrap(), Color::Black));
+        assert!(!game.is_square_attacked(Square::A8, Color::White));
+        assert_eq!(game.checkers().0, 0);
+
+        // Rook check along the back rank once the king is in line with it
+        let game = Game::from_fen("7k/8/8/8/8/8/8/4K2R b K - 0 1").unwrap();
+        assert_eq!(game.checkers().all_coords(), vec![Square::H1]);
+        assert!(game.in_check(Color::Black));
+        assert!(!game.in_check(Color::White));
+    }
+
+    #[test]
+    fn pins_and_evasions() {
+        // Knight pinned to the king can't move at all
+        let mut game = Game::from_fen("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            game.pinned(Color::White).all_coords(),
+            vec!["e2".parse().unwrap()]
+        );
+        assert_eq!(game.generate_moves().len(), 4);
+
+        // In check the rook can only block, the king can't stay on the file
+        let mut game = Game::from_fen("4k3/4r3/8/8/8/8/1R6/4K3 w - - 0 1").unwrap();
+        let mut moves: Vec<String> = game
+            .generate_moves()
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        moves.sort();
+        assert_eq!(moves, ["b2e2", "e1d1", "e1d2", "e1f1", "e1f2"]);
+    }
+
+    #[test]
+    fn bitboard_iteration() {
+        let bb = BitBoard::from_square(Square::A1)
+            | BitBoard::from_square(Square::E8)
+            | BitBoard::from_square(Square::H8);
+
+        assert_eq!(bb.count(), 3);
+        assert_eq!(bb.lsb(), Some(Square::A1));
+        assert_eq!(bb.msb(), Some(Square::H8));
+        assert_eq!(bb.collect::<Vec<_>>(), [Square::A1, Square::E8, Square::H8]);
+        assert!(BitBoard(0).is_empty());
+        assert_eq!(BitBoard(0).lsb(), None);
+        assert_eq!(BitBoard(0).msb(), None);
+    }
+
+    #[test]
+    fn move_list() {
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        let mut moves = game.generate_moves();
+        assert_eq!(moves.len(), 20);
+
+        moves.retain(|mv| mv.from.rank() == 1);
+        assert_eq!(moves.len(), 16);
+        assert_eq!(moves.into_iter().count(), 16);
+    }
+
+    #[test]
+    fn directional_shifts() {
+        // Nothing wraps from one edge file to the other
+        assert!(FILE_H.east().is_empty());
+        assert!(FILE_A.west().is_empty());
+        assert_eq!(FILE_A.east().west(), FILE_A);
+        assert_eq!(FILE_H.north_east().0, 0);
+        assert_eq!(BitBoard::from_square(Square::H8).north().0, 0);
+        assert_eq!(
+            BitBoard::from_square(Square::A1).north_east().lsb(),
+            "b2".parse().ok()
+        );
+
+        let game = Game::from_fen(START_FEN).unwrap();
+        let white = game.piece_bb(Color::White, Piece::Pawn);
+        let black = game.piece_bb(Color::Black, Piece::Pawn);
+        let empty = !(game.color_bb(Color::White) | game.color_bb(Color::Black));
+
+        assert_eq!(pawn_attacks_set(white, Color::White), BitBoard(0xFF << 16));
+        assert_eq!(pawn_attacks_set(black, Color::Black), BitBoard(0xFF << 40));
+        assert_eq!(
+            pawn_double_pushes(white, empty, Color::White),
+            BitBoard(0xFF << 24)
+        );
+        assert_eq!(
+            pawn_double_pushes(black, empty, Color::Black),
+            BitBoard(0xFF << 32)
+        );
+    }
+
+    #[test]
+    fn move_ordering() {
+        // Pawn takes queen beats rook takes queen, which beats rook takes pawn
+        let mut game = Game::from_fen("4k3/8/8/3q1p2/2P5/8/8/3RK3 w - - 0 1").unwrap();
+        let mut orderer = MoveOrderer::new();
+        let mut moves = game.generate_moves();
+
+        let quiet = moves
+            .iter()
+            .copied()
+            .find(|mv| mv.to_string() == "e1f2")
+            .unwrap();
+        orderer.cutoff(&game, quiet, 3, 4);
+        orderer.order(&game, &mut moves, 3, None);
+
+        let order: Vec<String> = moves.iter().map(|mv| mv.to_string()).collect();
+        assert_eq!(&order[..3], ["c4d5", "d1d5", "e1f2"]);
+
+        // The hash move goes ahead of everything
+        let hash_move = moves[5];
+        orderer.order(&game, &mut moves, 3, Some(hash_move));
+        assert_eq!(moves[0], hash_move);
+    }
+
+    #[test]
+    fn static_exchange() {
+        let see = |fen: &str, mv: &str| {
+            let mut game = Game::from_fen(fen).unwrap();
+            let mv = game
+                .generate_moves()
+                .into_iter()
+                .find(|m| m.to_string() == mv)
+                .unwrap();
+            game.see(mv)
+        };
+
+        // Pawn that nothing defends
+        assert_eq!(
+            see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"),
+            100
+        );
+        // Knight for a pawn once the pawn recaptures
+        assert_eq!(see("4k3/8/3p4/4p3/8/5N2/8/4K3 w - - 0 1", "f3e5"), -200);
+        // The queen behind the rook joins in through the x-ray
+        assert_eq!(see("4k3/4r3/8/4p3/8/8/4R3/4Q1K1 w - - 0 1", "e2e5"), 100);
+        assert_eq!(see("4k3/4r3/4r3/4p3/8/8/4R3/4K3 w - - 0 1", "e2e5"), -400);
+    }
+
+    #[test]
+    fn search_config() {
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut plain = Search::with_config(SearchConfig::none());
+        let mut pruned = Search::new();
+
+        // Pruning shouldn't hide a forced mate
+        for search in [&mut plain, &mut pruned] {
+            let mut game = Game::from_fen("7k/8/8/8/8/8/R7/1R5K w - - 0 1").unwrap();
+            assert_eq!(search.search(&mut game, Limits::depth(4)).score, MATE - 3);
+        }
+
+        let mut game = Game::from_fen(kiwipete).unwrap();
+        let plain_nodes = plain.search(&mut game, Limits::depth(4)).nodes;
+        let pruned_nodes = pruned.search(&mut game, Limits::depth(4)).nodes;
+        assert!(pruned_nodes < plain_nodes);
+    }
+
+    #[test]
+    fn threaded_search() {
+        let mut search = Search::new();
+        search.set_threads(3);
+
+        let mut game = Game::from_fen("7k/8/8/8/8/8/R7/1R5K w - - 0 1").unwrap();
+        let result = search.search(&mut game, Limits::depth(4));
+        assert_eq!(result.score, MATE - 3);
+
+        // A search with no limits runs until it is told to stop
+        let stop = search.stop_flag();
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        });
+
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        let result = search.search(&mut game, Limits::default());
+        stopper.join().unwrap();
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn multipv_and_clock() {
+        let mut search = Search::new();
+        search.set_multipv(3);
+
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        let mut depths = Vec::new();
+        let result = search.search_with_info(&mut game, Limits::depth(3), |r| depths.push(r.depth));
+
+        assert_eq!(depths, [1, 2, 3]);
+        assert_eq!(result.lines.len(), 3);
+        assert!(result.lines.windows(2).all(|w| w[0].score >= w[1].score));
+        assert_ne!(result.lines[0].pv[0], result.lines[1].pv[0]);
+
+        assert_eq!(mate_in(MATE - 3), Some(2));
+        assert_eq!(mate_in(-MATE + 2), Some(-1));
+        assert_eq!(mate_in(150), None);
+
+        let limits = Limits::clock(Duration::from_secs(60), Duration::from_secs(2), Some(20));
+        assert_eq!(limits.movetime, Some(Duration::from_millis(4500)));
+        let limits = Limits::clock(Duration::from_millis(30), Duration::ZERO, None);
+        assert_eq!(limits.movetime, Some(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn notation() {
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut game = Game::from_fen(kiwipete).unwrap();
+        assert_eq!(game.to_fen(), kiwipete);
+
+        let san = |game: &mut Game, text: &str| {
+            let mv = game.find_move(text).unwrap();
+            game.to_san(mv)
+        };
+        assert_eq!(san(&mut game, "e1g1"), "O-O");
+        assert_eq!(san(&mut game, "e5f7"), "Nxf7");
+        assert_eq!(san(&mut game, "d5e6"), "dxe6");
+        assert_eq!(san(&mut game, "c3b1"), "Nb1");
+        assert_eq!(san(&mut game, "f3f6"), "Qxf6");
+
+        // Pieces of the same kind that can reach the same square
+        let mut game = Game::from_fen("4k3/8/8/8/8/2N2N2/8/1NN1K3 w - - 0 1").unwrap();
+        assert_eq!(san(&mut game, "f3d2"), "Nfd2");
+        assert_eq!(san(&mut game, "c1e2"), "N1e2");
+        assert_eq!(san(&mut game, "c3e2"), "N3e2");
+        assert_eq!(san(&mut game, "f3g5"), "Ng5");
+        let mut game = Game::from_fen("4k3/8/8/8/R7/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(san(&mut game, "a1a2"), "R1a2");
+
+        let mut game = Game::from_fen("7k/1P6/6K1/8/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(san(&mut game, "b7b8q"), "b8=Q#");
+        assert_eq!(san(&mut game, "b7b8r"), "b8=R#");
+        assert_eq!(san(&mut game, "b7b8n"), "b8=N");
+
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        for text in ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Bxc6", "dxc6", "0-0"] {
+            let mv = game.parse_move(text).unwrap();
+            game.make_move(mv);
+        }
+        assert!(game.parse_san("Ke2").is_none());
+        assert_eq!(
+            game.parse_move("d8d3").map(|mv| mv.to_string()),
+            Some("d8d3".into())
+        );
+        assert_eq!(
+            game.to_fen(),
+            "r1bqkbnr/1pp2ppp/p1p5/4p3/4P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 1 5"
+        );
+
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        let mut pgn = Pgn::new(START_FEN);
+        for text in ["f3", "e5", "g4", "Qh4#"] {
+            let mv = game.parse_san(text).unwrap();
+            pgn.moves.push(mv);
+            game.make_move(mv);
+        }
+        pgn.result = "0-1".into();
+        pgn.set_tag("White", "Fool");
+        let pgn = pgn.to_string();
+        assert!(pgn.contains("[White \"Fool\"]\n"));
+        assert!(pgn.ends_with("\n1. f3 e5 2. g4 Qh4# 0-1\n"));
+
+        let board = format!("{:#}", Game::from_fen(START_FEN).unwrap());
+        assert!(board.starts_with("8  r n b q k b n r\n"));
+        assert!(format!("{}", game).contains("♛"));
+    }
+
+    #[test]
+    fn opening_book() {
+        let pgn = r#"
+            [Event "One"]
+            [Result "1-0"]
+            1. e4 e5 2. Nf3 {the main line} Nc6 (2... d6 3. d4) 3. Bb5 a6 $1 4. O-O 1-0
+
+            [Event "Two"]
+            [Result "1/2-1/2"]
+            1.e4 c5 2.Nf3 1/2-1/2
+
+            [Result "0-1"]
+            1. d4 d5 0-1
+
+            [Result "1-0"]
+            1. e4 e5 2. Qz9 1-0
+        "#;
+        let games = read_games(pgn);
+        assert_eq!(games.len(), 4);
+        assert_eq!(
+            games[3].as_ref().err(),
+            Some(&PgnError::IllegalMove("Qz9".into()))
+        );
+
+        let first = games[0].as_ref().unwrap();
+        assert_eq!(first.tag("Event"), Some("One"));
+        assert_eq!(first.result, "1-0");
+        assert_eq!(first.moves.len(), 7);
+        assert_eq!(first.moves[6].to_string(), "e1g1");
+        let mut castled = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let castle = castled.find_move("e1g1").unwrap();
+        assert_eq!(encode_move(&castled, castle), 0b000_000_100_000_111);
+        // A Chess960 king takes its rook wherever the rook started, here g1 not h1
+        let mut castled = Game::from_fen("1k6/8/8/8/8/8/8/RK4R1 w KQ - 0 1").unwrap();
+        let castle = castled.find_move("b1g1").unwrap();
+        assert_eq!(encode_move(&castled, castle), 0b000_000_001_000_110);
+
+        let mut builder = BookBuilder::new(4);
+        for game in games.iter().flatten() {
+            builder.add_game(game);
+        }
+        let book = Book::from_bytes(&builder.finish(1).to_bytes()).unwrap();
+
+        // e4 won once and drew once, d4 lost so it is left out
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        let moves: Vec<_> = book
+            .moves(&mut game)
+            .into_iter()
+            .map(|(mv, weight)| (mv.to_string(), weight))
+            .collect();
+        assert_eq!(moves, [("e2e4".to_string(), 3)]);
+
+        // Black lost with e5 but drew with c5
+        let e4 = game.parse_san("e4").unwrap();
+        game.make_move(e4);
+        assert_eq!(
+            book.choose_with(&mut game, 12345).map(|mv| mv.to_string()),
+            Some("c7c5".into())
+        );
+        for text in ["c5", "a3"] {
+            let mv = game.parse_san(text).unwrap();
+            game.make_move(mv);
+        }
+        assert!(book.choose(&mut game).is_none());
+
+        // A move in book is played without searching
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        let mut search = Search::new();
+        search.set_book(Some(std::sync::Arc::new(book)));
+        let result = search.search(&mut game, Limits::depth(4));
+        assert_eq!(result.best_move, Some(e4));
+        assert_eq!(result.nodes, 0);
+
+        // En passant only changes the key if it can be taken
+        let quiet = Game::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").unwrap();
+        let without = Game::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1").unwrap();
+        let capture = Game::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+        let capture_without = Game::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(polyglot_key(&quiet), polyglot_key(&without));
+        assert_ne!(polyglot_key(&capture), polyglot_key(&capture_without));
+
+        assert!(Book::from_bytes(&[0; 15]).is_err());
+    }
+
+    #[test]
+    fn polyglot_keys() {
+        // Keys given in the Polyglot book format description
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        assert_eq!(polyglot_key(&game), 0x463b96181691fc9c);
+        for (mv, key) in [
+            ("e2e4", 0x823c9b50fd114196),
+            ("d7d5", 0x0756b94461c50fb0),
+            ("e4e5", 0x662fafb965db29d4),
+            ("f7f5", 0x22a48b5a8e47ff78),
+            ("e1e2", 0x652a607ca3f242c1),
+            ("e8f7", 0x00fdd303c946bdd9),
+        ] {
+            let mv = game.find_move(mv).unwrap();
+            game.make_move(mv);
+            assert_eq!(polyglot_key(&game), key);
+        }
+
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        for mv in ["a2a4", "b7b5", "h2h4", "b5b4", "c2c4"] {
+            let mv = game.find_move(mv).unwrap();
+            game.make_move(mv);
+        }
+        assert_eq!(polyglot_key(&game), 0x3c8123ea7b067637);
+        for mv in ["b4c3", "a1a3"] {
+            let mv = game.find_move(mv).unwrap();
+            game.make_move(mv);
+        }
+        assert_eq!(polyglot_key(&game), 0x5c3f9b829b279560);
+    }
+
+    #[test]
+    fn tablebase() {
+        let mut tablebase = Tablebase::new();
+        tablebase.generate("KQK").unwrap();
+        // KPK needs the tables its promotions lead to, they're generated along with it
+        tablebase.generate("KKP").unwrap();
+        assert_eq!(tablebase.materials(), ["KBK", "KNK", "KPK", "KQK", "KRK"]);
+
+        let outcome =
+            |tablebase: &Tablebase, fen: &str| tablebase.outcome(&Game::from_fen(fen).unwrap());
+
+        // Mate in six, and the same with the colours the other way round
+        let kqk = "8/8/8/8/8/3k4/8/3K3Q w - - 0 1";
+        assert_eq!(outcome(&tablebase, kqk), Some(Outcome::Win(11)));
+        assert_eq!(
+            outcome(&tablebase, "3k3q/8/3K4/8/8/8/8/8 b - - 0 1"),
+            Some(Outcome::Win(11))
+        );
+        assert_eq!(
+            outcome(&tablebase, "k7/1Q6/1K6/8/8/8/8/8 b - - 0 1"),
+            Some(Outcome::Loss(0))
+        );
+        // Stalemate, and a rook pawn the defending king stands in front of
+        assert_eq!(
+            outcome(&tablebase, "k7/8/1QK5/8/8/8/8/8 b - - 0 1"),
+            Some(Outcome::Draw)
+        );
+        assert_eq!(
+            outcome(&tablebase, "k7/8/8/8/8/8/P7/K7 w - - 0 1"),
+            Some(Outcome::Draw)
+        );
+        assert_eq!(
+            outcome(&tablebase, "8/8/8/8/8/8/4k3/4K3 w - - 0 1"),
+            Some(Outcome::Draw)
+        );
+
+        // Not covered: too many pieces, or castling still possible
+        assert_eq!(outcome(&tablebase, START_FEN), None);
+        assert_eq!(outcome(&tablebase, "r3k3/8/8/8/8/8/8/4K3 b q - 0 1"), None);
+
+        let mut game = Game::from_fen(kqk).unwrap();
+        let (mv, result) = tablebase.best_move(&mut game).unwrap();
+        assert_eq!(result, Outcome::Win(11));
+        game.make_move(mv);
+        assert_eq!(tablebase.outcome(&game), Some(Outcome::Loss(10)));
+
+        let dir = std::env::temp_dir().join("chess_engine_tablebase_test");
+        tablebase.save(&dir).unwrap();
+        // Compressed well under the byte per position a table takes in memory, 64 * 64 * 10
+        // squares for the pieces by 2 sides to move for KQK
+        let size = std::fs::metadata(dir.join("KQK.tb")).unwrap().len();
+        assert!(size < 64 * 64 * 10 * 2 / 2, "{size}");
+        let loaded = Tablebase::load(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.materials(), tablebase.materials());
+        assert_eq!(outcome(&loaded, kqk), Some(Outcome::Win(11)));
+        assert_eq!(
+            outcome(&loaded, "8/8/8/8/8/8/4k3/4K3 w - - 0 1"),
+            Some(Outcome::Draw)
+        );
+
+        // The search plays straight from the tables
+        let mut game = Game::from_fen(kqk).unwrap();
+        let mut search = Search::new();
+        search.set_tablebase(Some(std::sync::Arc::new(loaded)));
+        let result = search.search(&mut game, Limits::depth(1));
+        assert_eq!(mate_in(result.score), Some(6));
+        assert_eq!(result.pv.len(), 11);
+
+        assert!(matches!(
+            Material::parse("KQ"),
+            Err(TablebaseError::InvalidMaterial(_))
+        ));
+        assert!(matches!(
+            Material::parse("KQRKP"),
+            Err(TablebaseError::TooManyPieces(_))
+        ));
+    }
+
+    #[test]
+    fn variants() {
+        // Atomic numbers from the variant perft suites, the others only differ deeper down
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        game.set_variant(&ATOMIC);
+        assert_eq!(game.perft(4), 197326);
+        assert_eq!(
+            variant::by_name("king-of-the-hill").unwrap().name(),
+            "King of the Hill"
+        );
+        assert!(variant::by_name("losers").is_none());
+
+        // The capturing piece and everything but pawns around the square go up, kings included
+        let fen = "4k3/3pq3/8/8/8/8/8/4RK2 w - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.set_variant(&ATOMIC);
+        let mv = game.find_move("e1e7").unwrap();
+        game.make_move(mv);
+        assert_eq!(game.to_fen(), "8/3p4/8/8/8/8/8/5K2 b - - 0 1");
+        assert_eq!(game.winner(), Some(Color::White));
+        assert!(game.generate_moves().is_empty());
+        game.unmake_move(mv);
+        assert_eq!(game.to_fen(), fen);
+
+        let result = Search::new().search(&mut game, Limits::depth(2));
+        assert_eq!(result.best_move, Some(mv));
+        assert_eq!(mate_in(result.score), Some(1));
+
+        // Kings can't capture, and touching kings can't give check
+        let mut game = Game::from_fen("8/8/8/8/8/8/5q2/4K2k w - - 0 1").unwrap();
+        game.set_variant(&ATOMIC);
+        assert!(game.find_move("e1f2").is_none());
+        let mut game = Game::from_fen("8/8/8/8/8/8/3kq3/4K3 w - - 0 1").unwrap();
+        game.set_variant(&ATOMIC);
+        assert!(game.checkers().is_empty());
+
+        let mut game = Game::from_fen("8/8/8/8/8/4K3/8/k7 w - - 0 1").unwrap();
+        game.set_variant(&KING_OF_THE_HILL);
+        let result = Search::new().search(&mut game, Limits::depth(2));
+        assert_eq!(mate_in(result.score), Some(1));
+        let mv = result.best_move.unwrap();
+        assert!(["e3d4", "e3e4"].contains(&mv.to_string().as_str()));
+        game.make_move(mv);
+        assert_eq!(game.winner(), Some(Color::White));
+
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        game.set_variant(&THREE_CHECK);
+        let mut moves = Vec::new();
+        for text in ["e4", "f6", "Qh5+", "g6", "Qxg6+", "hxg6", "Bc4", "a6"] {
+            let mv = game.parse_san(text).unwrap();
+            game.make_move(mv);
+            moves.push(mv);
+        }
+        assert_eq!(game.state().checks, [2, 0]);
+        assert_eq!(game.winner(), None);
+        let mv = game.parse_san("Bf7+").unwrap();
+        game.make_move(mv);
+        assert_eq!(game.winner(), Some(Color::White));
+        assert_eq!(game.hash(), game.compute_hash());
+
+        game.unmake_move(mv);
+        for &mv in moves.iter().rev() {
+            game.unmake_move(mv);
+        }
+        assert_eq!(game.state().checks, [0, 0]);
+        assert_eq!(game.to_fen(), START_FEN);
+    }
+
+    #[test]
+    fn crazyhouse() {
+        // Numbers from the Fairy-Stockfish perft suite
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        game.set_variant(&CRAZYHOUSE);
+        assert_eq!(game.perft(4), 197281);
+        let mut game = Game::from_fen("2k5/8/8/8/8/8/8/4K3[QRBNPqrbnp] w - - 0 1").unwrap();
+        game.set_variant(&CRAZYHOUSE);
+        assert_eq!(game.perft(1), 301);
+        assert_eq!(game.perft(2), 75353);
+
+        // Pockets go in brackets, ~ marks a promoted piece and a ninth rank works too
+        let fen = "r3k3/8/8/8/8/8/8/Q~3K3[Nbp] b - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.set_variant(&CRAZYHOUSE);
+        assert_eq!(game.to_fen(), fen);
+        let ranks = Game::from_fen("r3k3/8/8/8/8/8/8/Q~3K3/Nbp b - - 0 1").unwrap();
+        assert_eq!(ranks.hash(), game.hash());
+        assert_eq!(game.pocket(Color::Black, Piece::Bishop), 1);
+        assert_eq!(
+            Game::from_fen("4k3/8/8/8/8/8/8/4K3[K] w - - 0 1").err(),
+            Some(FenError::InvalidPocket)
+        );
+
+        // The promoted queen goes back in the pocket as a pawn
+        let capture = game.parse_san("Rxa1").unwrap();
+        game.make_move(capture);
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/r3K3[Nbpp] w - - 0 2");
+        assert_eq!(game.hash(), game.compute_hash());
+
+        // Drops can block a check but pawns can't go on the back rank
+        let moves = game.generate_moves();
+        let drops: Vec<String> = moves
+            .iter()
+            .filter(|mv| mv.dropped().is_some())
+            .map(|mv| mv.to_string())
+            .collect();
+        assert_eq!(drops, ["N@b1", "N@c1", "N@d1"]);
+        let drop = game.parse_san("N@c1").unwrap();
+        assert_eq!(game.to_san(drop), "N@c1");
+        game.make_move(drop);
+        assert_eq!(game.pocket(Color::White, Piece::Knight), 0);
+        assert_eq!(game.parse_san("@e4"), game.find_move("P@e4"));
+        assert!(game.find_move("P@e1").is_none());
+
+        game.unmake_move(drop);
+        game.unmake_move(capture);
+        assert_eq!(game.to_fen(), fen);
+
+        let tt = TranspositionTable::new(1);
+        tt.store(game.hash(), 1, 0, Bound::Exact, Some(drop));
+        assert_eq!(tt.probe(game.hash()).unwrap().best_move, Some(drop));
+
+        // In Bughouse the capture is left for the partner's board
+        let mut game = Game::from_fen("r3k3/8/8/8/8/8/8/Q~3K3[] b - - 0 1").unwrap();
+        game.set_variant(&BUGHOUSE);
+        let capture = game.parse_san("Rxa1").unwrap();
+        game.make_move(capture);
+        assert_eq!(game.pocketed(), Some(Piece::Pawn));
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/r3K3[] w - - 0 2");
+
+        let mut game = Game::from_fen("k7/8/1K6/8/8/8/8/8[Q] w - - 0 1").unwrap();
+        game.set_variant(&CRAZYHOUSE);
+        let result = Search::new().search(&mut game, Limits::depth(2));
+        assert_eq!(mate_in(result.score), Some(1));
+        assert!(result.best_move.unwrap().dropped().is_some());
+    }
+
+    #[test]
+    fn validate() {
+        for fen in [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/pppp1ppp/8/8/3pP3/8/PPP2PPP/RNBQKBNR b KQkq e3 0 3",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            "1k6/pppppppp/8/8/8/8/QQQQQQQQ/4K3 w - - 0 1",
+            "2k5/8/8/8/8/8/8/4K3[QRBNPqrbnp] w - - 0 1",
+        ] {
+            assert_eq!(Game::from_fen(fen).unwrap().validate(), Ok(()), "{fen}");
+        }
+        let mut game = Game::new();
+        game.init(123);
+        assert_eq!(game.validate(), Ok(()));
+
+        for (fen, error) in [
+            (
+                "8/8/8/8/8/8/8/4K3 w - - 0 1",
+                PositionError::KingCount(Color::Black, 0),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/3KK3 w - - 0 1",
+                PositionError::KingCount(Color::White, 2),
+            ),
+            (
+                "4k2P/8/8/8/8/8/8/4K3 w - - 0 1",
+                PositionError::PawnOnBackRank(Square::H8),
+            ),
+            (
+                "R3k3/8/8/8/8/8/8/4K3 w - - 0 1",
+                PositionError::OpponentInCheck,
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K2R w Q - 0 1",
+                PositionError::InvalidCastling(Color::White, false),
+            ),
+            (
+                "r3k3/8/8/8/8/8/8/4K3 w k - 0 1",
+                PositionError::InvalidCastling(Color::Black, true),
+            ),
+            (
+                "4k3/8/8/8/4P3/8/8/4K3 b - e4 0 1",
+                PositionError::InvalidEnPassant(Square::ALL[28]),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 b - e3 0 1",
+                PositionError::InvalidEnPassant(Square::ALL[20]),
+            ),
+            (
+                "4k3/8/8/8/8/PPPPPPPP/PPPPPPPP/4K3 w - - 0 1",
+                PositionError::TooManyPieces(Color::White),
+            ),
+            (
+                "4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - - 0 1",
+                PositionError::TooManyPawns(Color::White),
+            ),
+            (
+                "qqqqk3/pppppp2/8/8/8/8/8/4K3 w - - 0 1",
+                PositionError::TooManyPromotions(Color::Black),
+            ),
+        ] {
+            let game = Game::from_fen(fen).unwrap();
+            assert_eq!(game.validate(), Err(error), "{fen}");
+        }
+
+        // A king off the e-file can only castle in Chess960, which the FEN turns on
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/3K3R w K - 0 1").unwrap();
+        assert_eq!(game.validate(), Ok(()));
+        game.set_chess960(false);
+        assert_eq!(
+            game.validate(),
+            Err(PositionError::InvalidCastling(Color::White, true))
+        );
+    }
+
+    #[test]
+    fn editor() {
+        let mut game = Game::new();
+        game.init(STANDARD_POSITION);
+        let mv = game.find_move("e2e4").unwrap();
+        game.make_move(mv);
+
+        // Queen odds, black to move after 1. e4 with black's queen gone
+        assert_eq!(
+            game.clear_square(Square::D8),
+            Some((Color::Black, Piece::Queen))
+        );
+        assert_eq!(game.clear_square(Square::D8), None);
+        let fen = "rnb1kbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        assert_eq!(game.to_fen(), fen);
+        assert_eq!(game.hash(), Game::from_fen(fen).unwrap().hash());
+        assert_eq!(game.validate(), Ok(()));
+
+        game.clear_board();
+        assert_eq!(game.to_fen(), "8/8/8/8/8/8/8/8 b - - 0 1");
+        assert_eq!(game.hash(), game.compute_hash());
+
+        for (side, piece, sqr) in [
+            (Color::White, Piece::King, Square::E1),
+            (Color::White, Piece::Rook, Square::H1),
+            (Color::Black, Piece::King, Square::E8),
+            (Color::Black, Piece::Rook, Square::ALL[28]),
+            (Color::White, Piece::Queen, Square::ALL[28]),
+        ] {
+            game.place_piece(side, piece, sqr);
+        }
+        game.set_castling(Color::White, true, true);
+        game.set_castling(Color::Black, false, true);
+        game.set_castling(Color::Black, false, false);
+        let fen = "4k3/8/8/8/4Q3/8/8/4K2R b K - 0 1";
+        assert_eq!(game.to_fen(), fen);
+        assert_eq!(game.hash(), Game::from_fen(fen).unwrap().hash());
+        for sqr in Square::ALL {
+            let expected = Color::ALL.into_iter().find_map(|side| {
+                Piece::ALL
+                    .into_iter()
+                    .find(|&piece| {
+                        !(game.piece_bb(side, piece) & BitBoard::from_square(sqr)).is_empty()
+                    })
+                    .map(|piece| (side, piece))
+            });
+            assert_eq!(game.piece_at(sqr), expected);
+        }
+        assert_eq!(game.checkers(), BitBoard::from_square(Square::ALL[28]));
+        assert_eq!(game.validate(), Ok(()));
+        assert_eq!(game.perft(3), Game::from_fen(fen).unwrap().perft(3));
+
+        game.set_turn(Color::White);
+        assert_eq!(game.validate(), Err(PositionError::OpponentInCheck));
+        assert_eq!(game.hash(), game.compute_hash());
+    }
+
+    #[test]
+    #[should_panic]
+    fn full_move_list() {
+        let mut list = MoveList::new();
+        let mv = Move::new(Square::ALL[12], Square::ALL[28], LegalMove::Move);
+        for _ in 0..MAX_MOVES {
+            list.push(mv);
+        }
+        assert_eq!(list.len(), MAX_MOVES);
+        list.push(mv);
+    }
+}
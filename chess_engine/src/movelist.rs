@@ -0,0 +1,112 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::{
+    game::{LegalMove, Move},
+    square::Square,
+};
+
+// No legal position has more moves than this, drops of five kinds of piece on every
+// empty square plus the most moves a normal chess position can have
+pub const MAX_MOVES: usize = 600;
+
+/// Fixed capacity list of moves kept on the stack so move generation doesn't allocate
+#[derive(Clone)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize,
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MoveList {
+    pub fn new() -> Self {
+        Self {
+            moves: [Move::new(Square::A1, Square::A1, LegalMove::Move); MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    /// Add a move to the end. Only a position that was never validated, e.g. straight from
+    /// a FEN, could fill the list
+    pub fn push(&mut self, mv: Move) {
+        debug_assert!(
+            self.len < MAX_MOVES,
+            "More than {MAX_MOVES} moves generated"
+        );
+        self.moves[self.len] = mv;
+        self.len += 1;
+    }
+
+    /// Keep only the moves matching a condition, in their original order
+    pub fn retain(&mut self, mut keep: impl FnMut(&Move) -> bool) {
+        let mut len = 0;
+        for i in 0..self.len {
+            if keep(&self.moves[i]) {
+                self.moves[len] = self.moves[i];
+                len += 1;
+            }
+        }
+        self.len = len;
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [Move];
+
+    fn deref(&self) -> &Self::Target {
+        &self.moves[..self.len]
+    }
+}
+
+impl DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.moves[..self.len]
+    }
+}
+
+pub struct IntoIter {
+    list: MoveList,
+    index: usize,
+}
+
+impl Iterator for IntoIter {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mv = self.list.get(self.index).copied()?;
+        self.index += 1;
+        Some(mv)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.list.len - self.index;
+        (left, Some(left))
+    }
+}
+
+impl ExactSizeIterator for IntoIter {}
+
+impl IntoIterator for MoveList {
+    type Item = Move;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            list: self,
+            index: 0,
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
@@ -0,0 +1,123 @@
+use std::cmp::Reverse;
+
+use crate::{
+    eval::PIECE_VALUES_MG,
+    game::{Game, Move},
+    movelist::MoveList,
+    piece::{Color, Piece},
+    search::MAX_DEPTH,
+};
+
+// Sort keys, each kind of move is always tried before the kinds below it
+const HASH_MOVE: i32 = 1_000_000;
+const GOOD_CAPTURE: i32 = 100_000;
+const KILLER: [i32; 2] = [90_000, 80_000];
+// History is halved once it gets this big so it stays under the killers
+const HISTORY_MAX: i32 = 50_000;
+
+/// Remembers which quiet moves caused cutoffs so they can be tried early next time
+pub struct MoveOrderer {
+    // Two quiet moves per ply that caused a cutoff in a sibling node
+    killers: [[Option<Move>; 2]; MAX_DEPTH as usize + 1],
+    // How often a piece moving to a square caused a cutoff, weighted by depth
+    history: [[[i32; 64]; Piece::COUNT]; Color::COUNT],
+}
+
+impl Default for MoveOrderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MoveOrderer {
+    pub fn new() -> Self {
+        Self {
+            killers: [[None; 2]; MAX_DEPTH as usize + 1],
+            history: [[[0; 64]; Piece::COUNT]; Color::COUNT],
+        }
+    }
+
+    /// Forget everything, for a new game
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Killers only make sense within one search, history is kept but counts for less
+    pub fn new_search(&mut self) {
+        self.killers = [[None; 2]; MAX_DEPTH as usize + 1];
+        self.age_history();
+    }
+
+    fn age_history(&mut self) {
+        for score in self.history.iter_mut().flatten().flatten() {
+            *score /= 2;
+        }
+    }
+
+    /// Sort moves best first, must be called before any of them are made
+    pub fn order(&self, game: &Game, moves: &mut MoveList, ply: u8, hash_move: Option<Move>) {
+        moves.sort_unstable_by_key(|&mv| Reverse(self.score(game, mv, ply, hash_move)));
+    }
+
+    fn score(&self, game: &Game, mv: Move, ply: u8, hash_move: Option<Move>) -> i32 {
+        if Some(mv) == hash_move {
+            return HASH_MOVE;
+        }
+
+        if game.is_capture(mv) || mv.promotion.is_some() {
+            return GOOD_CAPTURE + mvv_lva(game, mv);
+        }
+
+        if let Some(slot) = self.killers[ply as usize]
+            .iter()
+            .position(|&k| k == Some(mv))
+        {
+            return KILLER[slot];
+        }
+
+        match game.moving_piece(mv) {
+            Some((color, piece)) => self.history[color as usize][piece as usize][mv.to.index()],
+            None => 0,
+        }
+    }
+
+    /// Record a quiet move that caused a beta cutoff, call after it has been unmade
+    pub fn cutoff(&mut self, game: &Game, mv: Move, ply: u8, depth: u8) {
+        if game.is_capture(mv) || mv.promotion.is_some() {
+            return;
+        }
+
+        let killers = &mut self.killers[ply as usize];
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+
+        if let Some((color, piece)) = game.moving_piece(mv) {
+            let score = &mut self.history[color as usize][piece as usize][mv.to.index()];
+            *score += depth as i32 * depth as i32;
+
+            if *score > HISTORY_MAX {
+                self.age_history();
+            }
+        }
+    }
+}
+
+/// Most valuable victim, least valuable attacker, taking a queen with a pawn scores highest
+pub fn mvv_lva(game: &Game, mv: Move) -> i32 {
+    let victim = match game.piece_at(mv.to) {
+        Some((_, piece)) => PIECE_VALUES_MG[piece as usize],
+        // En passant or a quiet promotion
+        None if game.is_capture(mv) => PIECE_VALUES_MG[Piece::Pawn as usize],
+        None => 0,
+    };
+    let attacker = game
+        .piece_at(mv.from)
+        .map_or(0, |(_, piece)| PIECE_VALUES_MG[piece as usize]);
+    let promotion = mv
+        .promotion
+        .map_or(0, |piece| PIECE_VALUES_MG[piece as usize]);
+
+    victim * 10 + promotion - attacker
+}
@@ -0,0 +1,261 @@
+use std::fmt::Display;
+
+use crate::{
+    fen::{FenError, START_FEN},
+    game::{Game, Move},
+    piece::Color,
+};
+
+// Tokens that end a game's movetext
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PgnError {
+    InvalidFen(FenError),
+    IllegalMove(String),
+}
+
+impl Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnError::InvalidFen(err) => write!(f, "Invalid FEN tag: {err}"),
+            PgnError::IllegalMove(text) => write!(f, "Illegal move {text}"),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+// Export format keeps movetext lines under this many characters
+const LINE_WIDTH: usize = 80;
+
+/// A game in Portable Game Notation
+pub struct Pgn {
+    // Written in this order, the seven required tags come first
+    tags: Vec<(String, String)>,
+    start_fen: String,
+    pub moves: Vec<Move>,
+    // 1-0, 0-1, 1/2-1/2 or * for a game still going
+    pub result: String,
+}
+
+impl Pgn {
+    /// Empty game from a position, the required tags are filled with unknown values
+    pub fn new(start_fen: &str) -> Self {
+        let tags = [
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "?"),
+            ("White", "?"),
+            ("Black", "?"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+
+        Self {
+            tags,
+            start_fen: start_fen.to_string(),
+            moves: Vec::new(),
+            result: "*".to_string(),
+        }
+    }
+
+    /// Value of a tag, the result and starting position have their own fields
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn start_fen(&self) -> &str {
+        &self.start_fen
+    }
+
+    /// Set a tag, replacing its value if it is already there
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+}
+
+impl Display for Pgn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut game = Game::from_fen(&self.start_fen).map_err(|_| std::fmt::Error)?;
+
+        for (name, value) in &self.tags {
+            writeln!(
+                f,
+                "[{name} \"{}\"]",
+                value.replace('\\', "\\\\").replace('"', "\\\"")
+            )?;
+        }
+        writeln!(f, "[Result \"{}\"]", self.result)?;
+        if self.start_fen != START_FEN {
+            writeln!(f, "[SetUp \"1\"]")?;
+            writeln!(f, "[FEN \"{}\"]", self.start_fen)?;
+        }
+        writeln!(f)?;
+
+        let mut tokens = Vec::new();
+        for (i, &mv) in self.moves.iter().enumerate() {
+            let number = game.state().fullmove_number;
+            match game.turn() {
+                Color::White => tokens.push(format!("{number}.")),
+                Color::Black if i == 0 => tokens.push(format!("{number}...")),
+                Color::Black => {}
+            }
+
+            tokens.push(game.to_san(mv));
+            game.make_move(mv);
+        }
+        tokens.push(self.result.clone());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+                writeln!(f, "{line}")?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        writeln!(f, "{line}")
+    }
+}
+
+/// Read every game in a PGN file. A game that can't be read gives an error without
+/// stopping the ones after it. Comments, variations and annotations are skipped
+pub fn read_games(text: &str) -> Vec<Result<Pgn, PgnError>> {
+    let mut games = Vec::new();
+    let mut tags = Vec::new();
+    // Game whose moves are being read, and the position they have got to
+    let mut current: Option<Result<(Pgn, Game), PgnError>> = None;
+
+    for token in tokenize(text) {
+        match token {
+            Token::Tag(name, value) => {
+                // Tags after some moves start the next game even without a result
+                if let Some(game) = current.take() {
+                    games.push(game.map(|(pgn, _)| pgn));
+                }
+                tags.push((name, value));
+            }
+            Token::Word(word) if RESULTS.contains(&word.as_str()) => {
+                let game = current
+                    .take()
+                    .unwrap_or_else(|| start_game(std::mem::take(&mut tags)));
+                games.push(game.map(|(mut pgn, _)| {
+                    pgn.result = word;
+                    pgn
+                }));
+            }
+            Token::Word(word) => {
+                let game = current.get_or_insert_with(|| start_game(std::mem::take(&mut tags)));
+                let Ok((pgn, game)) = game else {
+                    continue;
+                };
+
+                match game.parse_san(&word) {
+                    Some(mv) => {
+                        game.make_move(mv);
+                        pgn.moves.push(mv);
+                    }
+                    None => current = Some(Err(PgnError::IllegalMove(word))),
+                }
+            }
+        }
+    }
+
+    if let Some(game) = current {
+        games.push(game.map(|(pgn, _)| pgn));
+    }
+    games
+}
+
+fn start_game(tags: Vec<(String, String)>) -> Result<(Pgn, Game), PgnError> {
+    let fen = tags
+        .iter()
+        .find(|(name, _)| name == "FEN")
+        .map_or(START_FEN, |(_, value)| value.as_str());
+    let game = Game::from_fen(fen).map_err(PgnError::InvalidFen)?;
+
+    let mut pgn = Pgn::new(fen);
+    for (name, value) in &tags {
+        match name.as_str() {
+            "Result" | "SetUp" | "FEN" => {}
+            _ => pgn.set_tag(name, value),
+        }
+    }
+    Ok((pgn, game))
+}
+
+enum Token {
+    Tag(String, String),
+    // A move or a result, move numbers are left out
+    Word(String),
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    // How many variations deep we are, their moves are skipped
+    let mut variation: u32 = 0;
+
+    while let Some(chr) = chars.next() {
+        match chr {
+            '{' => {
+                chars.by_ref().find(|&c| c == '}');
+            }
+            ';' => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            '(' => variation += 1,
+            ')' => variation = variation.saturating_sub(1),
+            '[' => {
+                let mut tag = String::new();
+                let mut quoted = false;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' if quoted => tag.extend(chars.next()),
+                        '"' => quoted = !quoted,
+                        ']' if !quoted => break,
+                        _ => tag.push(c),
+                    }
+                }
+
+                if let Some((name, value)) = tag.trim().split_once(char::is_whitespace) {
+                    tokens.push(Token::Tag(name.to_string(), value.trim().to_string()));
+                }
+            }
+            chr if chr.is_whitespace() => {}
+            chr => {
+                let mut word = chr.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "{;()[".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+
+                // Move numbers can be stuck to the move e.g. 12.e4 or 12...Nf6
+                let word = match word.find(|c: char| !c.is_ascii_digit()) {
+                    Some(i) if word[i..].starts_with('.') => word[i..].trim_start_matches('.'),
+                    _ => &word,
+                };
+                if variation == 0 && !word.is_empty() && !word.starts_with('$') {
+                    tokens.push(Token::Word(word.to_string()));
+                }
+            }
+        }
+    }
+
+    tokens
+}
@@ -0,0 +1,120 @@
+use std::fmt::Display;
+use std::ops::Not;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Piece {
+    Pawn = 0,
+    Rook = 1,
+    Knight = 2,
+    Bishop = 3,
+    Queen = 4,
+    King = 5,
+}
+
+impl Piece {
+    pub const COUNT: usize = 6;
+    pub const ALL: [Piece; Piece::COUNT] = [
+        Piece::Pawn,
+        Piece::Rook,
+        Piece::Knight,
+        Piece::Bishop,
+        Piece::Queen,
+        Piece::King,
+    ];
+    // Pieces a pawn can promote to, best first
+    pub const PROMOTIONS: [Piece; 4] = [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight];
+    // Pieces that can be held in a Crazyhouse pocket, in the order FEN lists them
+    pub const POCKET: [Piece; 5] = [
+        Piece::Queen,
+        Piece::Rook,
+        Piece::Bishop,
+        Piece::Knight,
+        Piece::Pawn,
+    ];
+
+    /// Parse a piece letter in either case e.g. 'N' or 'n'
+    pub fn from_char(chr: char) -> Option<Self> {
+        match chr.to_ascii_lowercase() {
+            'p' => Some(Piece::Pawn),
+            'r' => Some(Piece::Rook),
+            'n' => Some(Piece::Knight),
+            'b' => Some(Piece::Bishop),
+            'q' => Some(Piece::Queen),
+            'k' => Some(Piece::King),
+            _ => None,
+        }
+    }
+
+    /// Lowercase piece letter
+    pub fn to_char(self) -> char {
+        match self {
+            Piece::Pawn => 'p',
+            Piece::Rook => 'r',
+            Piece::Knight => 'n',
+            Piece::Bishop => 'b',
+            Piece::Queen => 'q',
+            Piece::King => 'k',
+        }
+    }
+
+    /// Chess symbol for the piece in the given colour e.g. ♘ for a white knight
+    pub fn to_unicode(self, side: Color) -> char {
+        let symbols = match side {
+            Color::White => ['♙', '♖', '♘', '♗', '♕', '♔'],
+            Color::Black => ['♟', '♜', '♞', '♝', '♛', '♚'],
+        };
+        symbols[self as usize]
+    }
+}
+
+impl Display for Piece {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Color {
+    White = 0,
+    Black = 1,
+}
+
+impl Color {
+    pub const COUNT: usize = 2;
+    pub const ALL: [Color; Color::COUNT] = [Color::White, Color::Black];
+
+    /// Direction pawns move in, up the board for white
+    pub fn forward(self) -> i8 {
+        match self {
+            Color::White => 1,
+            Color::Black => -1,
+        }
+    }
+
+    /// Rank the back row of pieces start on
+    pub fn back_rank(self) -> u8 {
+        match self {
+            Color::White => 0,
+            Color::Black => 7,
+        }
+    }
+}
+
+impl Not for Color {
+    type Output = Self;
+    fn not(self) -> Self::Output {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
+
+impl Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Color::White => write!(f, "white"),
+            Color::Black => write!(f, "black"),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{
+    game::{Game, LegalMove, Move},
+    movelist::MoveList,
+    piece::Piece,
+};
+
+impl Game {
+    /// Standard Algebraic Notation for a legal move e.g. Nf3, exd5, O-O, e8=Q+ or N@f3
+    pub fn to_san(&mut self, mv: Move) -> String {
+        let moves = self.generate_moves();
+        let mut san = self.san_without_check(mv, &moves);
+
+        self.make_move(mv);
+        if !self.checkers().is_empty() {
+            san.push(if self.generate_moves().is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+        self.unmake_move(mv);
+
+        san
+    }
+
+    /// Legal move matching Standard Algebraic Notation. Check marks, annotations and the
+    /// capture and promotion signs are optional
+    pub fn parse_san(&mut self, text: &str) -> Option<Move> {
+        let wanted = normalize_san(text);
+        if wanted.is_empty() {
+            return None;
+        }
+
+        let moves = self.generate_moves();
+        moves
+            .iter()
+            .copied()
+            .find(|&mv| normalize_san(&self.san_without_check(mv, &moves)) == wanted)
+    }
+
+    /// Legal move in either coordinate notation or SAN
+    pub fn parse_move(&mut self, text: &str) -> Option<Move> {
+        self.find_move(text).or_else(|| self.parse_san(text))
+    }
+
+    // SAN without the check mark, moves is every legal move in the position
+    fn san_without_check(&self, mv: Move, moves: &MoveList) -> String {
+        if mv.kind == LegalMove::Castle {
+            return if mv.to.file() == 6 { "O-O" } else { "O-O-O" }.to_string();
+        }
+        if mv.dropped().is_some() {
+            return mv.to_string();
+        }
+
+        let Some((_, piece)) = self.piece_at(mv.from) else {
+            return mv.to_string();
+        };
+        let capture = self.is_capture(mv);
+        let mut san = String::new();
+
+        if piece == Piece::Pawn {
+            if capture {
+                san.push(file_char(mv.from.file()));
+            }
+        } else {
+            san.push(piece.to_char().to_ascii_uppercase());
+
+            // Other pieces of the same kind that could also go to the target square
+            let rivals: Vec<Move> = moves
+                .iter()
+                .copied()
+                .filter(|other| {
+                    other.to == mv.to
+                        && other.from != mv.from
+                        && self.piece_at(other.from).map(|(_, p)| p) == Some(piece)
+                })
+                .collect();
+
+            if !rivals.is_empty() {
+                let same_file = rivals.iter().any(|o| o.from.file() == mv.from.file());
+                let same_rank = rivals.iter().any(|o| o.from.rank() == mv.from.rank());
+
+                if !same_file {
+                    san.push(file_char(mv.from.file()));
+                } else if !same_rank {
+                    san.push(rank_char(mv.from.rank()));
+                } else {
+                    san.push_str(&mv.from.to_string());
+                }
+            }
+        }
+
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&mv.to.to_string());
+
+        if let Some(promotion) = mv.promotion {
+            san.push('=');
+            san.push(promotion.to_char().to_ascii_uppercase());
+        }
+
+        san
+    }
+}
+
+fn file_char(file: u8) -> char {
+    (b'a' + file) as char
+}
+
+fn rank_char(rank: u8) -> char {
+    (b'1' + rank) as char
+}
+
+// Strip the parts of SAN people often leave off or write differently
+fn normalize_san(text: &str) -> String {
+    let text = text.trim().trim_end_matches("e.p.").trim();
+    // Pawn drops are sometimes written without the P
+    let text = match text.strip_prefix('@') {
+        Some(rest) => format!("P@{rest}"),
+        None => text.to_string(),
+    };
+    text.replace('0', "O")
+        .chars()
+        .filter(|chr| !matches!(chr, 'x' | '=' | '+' | '#' | '!' | '?'))
+        .collect()
+}
@@ -0,0 +1,788 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    book::Book,
+    eval::evaluate,
+    game::{Game, Move},
+    ordering::MoveOrderer,
+    piece::{Color, Piece},
+    tablebase::{Outcome, Tablebase},
+    tt::{Bound, TranspositionTable},
+};
+
+pub const INFINITY: i32 = 32_000;
+pub const MATE: i32 = 30_000;
+pub const MAX_DEPTH: u8 = 64;
+// Scores beyond this are mates, which depend on the ply they were found at. Tablebase
+// mates can be a long way past the end of the search
+const MATE_BOUND: i32 = MATE - 512;
+
+// How often in nodes the clock is checked
+const CHECK_INTERVAL: u64 = 2048;
+
+// Half width of the first aspiration window, doubled on every fail
+const ASPIRATION_WINDOW: i32 = 25;
+// Margins by remaining depth, a quiet move is unlikely to gain more than this
+const FUTILITY_MARGIN: [i32; 3] = [0, 200, 400];
+// Moves searched at full depth before the rest are reduced
+const LMR_FULL_MOVES: usize = 3;
+
+/// Switches for the search enhancements so they can be turned off and compared
+#[derive(Clone, Copy, Debug)]
+pub struct SearchConfig {
+    pub null_move: bool,
+    pub late_move_reductions: bool,
+    pub futility: bool,
+    pub check_extensions: bool,
+    pub aspiration: bool,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            null_move: true,
+            late_move_reductions: true,
+            futility: true,
+            check_extensions: true,
+            aspiration: true,
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Plain alpha-beta with nothing switched on
+    pub fn none() -> Self {
+        Self {
+            null_move: false,
+            late_move_reductions: false,
+            futility: false,
+            check_extensions: false,
+            aspiration: false,
+        }
+    }
+}
+
+/// When to stop searching, whichever limit is hit first wins
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Limits {
+    pub depth: Option<u8>,
+    pub movetime: Option<Duration>,
+}
+
+impl Limits {
+    pub fn depth(depth: u8) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    pub fn movetime(movetime: Duration) -> Self {
+        Self {
+            movetime: Some(movetime),
+            ..Default::default()
+        }
+    }
+
+    /// Share out the time left on a clock between the moves still to play, assuming 30
+    /// more if there is no time control
+    pub fn clock(remaining: Duration, increment: Duration, moves_to_go: Option<u32>) -> Self {
+        let moves = moves_to_go.unwrap_or(30).max(1);
+        // Keep a little back so lag never loses on time
+        let safe = remaining.saturating_sub(Duration::from_millis(50));
+        let movetime = (remaining / moves + increment * 3 / 4).min(safe);
+
+        Self::movetime(movetime.max(Duration::from_millis(1)))
+    }
+}
+
+/// One of the best lines at the root, more than one when searching with MultiPV
+#[derive(Clone, Debug)]
+pub struct Line {
+    pub score: i32,
+    pub pv: Vec<Move>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    // Centipawns from the side to move's point of view
+    pub score: i32,
+    // Principal variation, the line both sides are expected to play
+    pub pv: Vec<Move>,
+    pub depth: u8,
+    pub nodes: u64,
+    // Every line searched, best first, the first is the same as score and pv
+    pub lines: Vec<Line>,
+}
+
+/// Moves to mate from a score, negative when the side to move is getting mated
+pub fn mate_in(score: i32) -> Option<i32> {
+    if score >= MATE_BOUND {
+        Some((MATE - score + 1) / 2)
+    } else if score <= -MATE_BOUND {
+        Some(-(MATE + score) / 2)
+    } else {
+        None
+    }
+}
+
+/// Runs searches, holding what is kept between them: the transposition table and move
+/// ordering history. Any number of helper threads can search alongside the main one,
+/// sharing the transposition table (Lazy SMP)
+pub struct Search {
+    tt: TranspositionTable,
+    ordering: MoveOrderer,
+    config: SearchConfig,
+    threads: usize,
+    multipv: usize,
+    // Moves come from here instead of searching while the position is in the book
+    book: Option<Arc<Book>>,
+    // Endings in here are looked up instead of searched
+    tablebase: Option<Arc<Tablebase>>,
+    stop: Arc<AtomicBool>,
+}
+
+// One thread's part in a search
+struct Worker<'a> {
+    tt: &'a TranspositionTable,
+    stop: &'a AtomicBool,
+    tablebase: Option<&'a Tablebase>,
+    ordering: MoveOrderer,
+    config: SearchConfig,
+    limits: Limits,
+    multipv: usize,
+    // Root moves left out so the next best line can be found
+    excluded: Vec<Move>,
+    start: Instant,
+    nodes: u64,
+    // Node count at which the clock and stop flag are next looked at
+    next_check: u64,
+    stopped: bool,
+    // Only the main thread watches the clock and reports a result
+    main: bool,
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Self::with_config(SearchConfig::default())
+    }
+
+    pub fn with_config(config: SearchConfig) -> Self {
+        Self {
+            tt: TranspositionTable::default(),
+            ordering: MoveOrderer::new(),
+            config,
+            threads: 1,
+            multipv: 1,
+            book: None,
+            tablebase: None,
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn config(&self) -> SearchConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: SearchConfig) {
+        self.config = config;
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Number of threads to search with, including the main one
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Number of best lines to find, each one is a full search of the root
+    pub fn set_multipv(&mut self, lines: usize) {
+        self.multipv = lines.max(1);
+    }
+
+    /// Opening book to play from before searching, None to always search
+    pub fn set_book(&mut self, book: Option<Arc<Book>>) {
+        self.book = book;
+    }
+
+    /// Endgame tables to probe, positions they cover are played perfectly
+    pub fn set_tablebase(&mut self, tablebase: Option<Arc<Tablebase>>) {
+        self.tablebase = tablebase;
+    }
+
+    /// Set the transposition table size in MB, clearing it
+    pub fn set_hash_size(&mut self, size_mb: usize) {
+        self.tt.resize(size_mb);
+    }
+
+    /// Forget everything learnt from the previous game
+    pub fn new_game(&mut self) {
+        self.tt.clear();
+        self.ordering.clear();
+    }
+
+    /// Flag that ends the current search as soon as it is set, for stopping a search
+    /// running on another thread. It is cleared when a search finishes
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    /// Iterative deepening search for the best move in a position
+    pub fn search(&mut self, game: &mut Game, limits: Limits) -> SearchResult {
+        self.search_with_info(game, limits, |_| {})
+    }
+
+    /// Search calling `info` with the result so far after every depth is finished
+    pub fn search_with_info(
+        &mut self,
+        game: &mut Game,
+        limits: Limits,
+        mut info: impl FnMut(&SearchResult),
+    ) -> SearchResult {
+        // Books and tablebases are only any use under the normal rules
+        let book = self.book.as_ref().filter(|_| game.is_standard());
+        if let Some(mv) = book.and_then(|book| book.choose(game)) {
+            let result = SearchResult {
+                best_move: Some(mv),
+                score: 0,
+                pv: vec![mv],
+                depth: 0,
+                nodes: 0,
+                lines: vec![Line {
+                    score: 0,
+                    pv: vec![mv],
+                }],
+            };
+            info(&result);
+            self.stop.store(false, Ordering::Relaxed);
+            return result;
+        }
+
+        if let Some(result) = self
+            .tablebase
+            .as_ref()
+            .filter(|_| game.is_standard())
+            .and_then(|tb| tablebase_root(tb, game))
+        {
+            info(&result);
+            self.stop.store(false, Ordering::Relaxed);
+            return result;
+        }
+
+        self.tt.new_search();
+        self.ordering.new_search();
+
+        let start = Instant::now();
+        let ordering = std::mem::take(&mut self.ordering);
+        let (tt, stop, config) = (&self.tt, &*self.stop, self.config);
+        let tablebase = self.tablebase.as_deref();
+        let multipv = self.multipv;
+
+        let (mut result, ordering, helper_nodes) = thread::scope(|scope| {
+            let helpers: Vec<_> = (1..self.threads)
+                .map(|id| {
+                    let mut game = game.clone();
+                    scope.spawn(move || {
+                        let mut worker =
+                            Worker::new(tt, stop, MoveOrderer::new(), config, limits, start);
+                        worker.tablebase = tablebase;
+                        // Have half the helpers a depth ahead so the threads spread out
+                        worker.iterate(&mut game, 1 + (id % 2) as u8, &mut |_| {});
+                        worker.nodes
+                    })
+                })
+                .collect();
+
+            let mut main = Worker::new(tt, stop, ordering, config, limits, start);
+            main.main = true;
+            main.tablebase = tablebase;
+            main.multipv = multipv;
+            let result = main.iterate(game, 1, &mut info);
+
+            // Helpers only finish by being told to
+            stop.store(true, Ordering::Relaxed);
+            let nodes: u64 = helpers
+                .into_iter()
+                .map(|helper| helper.join().expect("Search thread panicked"))
+                .sum();
+            (result, main.ordering, nodes)
+        });
+
+        self.ordering = ordering;
+        self.stop.store(false, Ordering::Relaxed);
+        result.nodes += helper_nodes;
+        result
+    }
+}
+
+impl<'a> Worker<'a> {
+    fn new(
+        tt: &'a TranspositionTable,
+        stop: &'a AtomicBool,
+        ordering: MoveOrderer,
+        config: SearchConfig,
+        limits: Limits,
+        start: Instant,
+    ) -> Self {
+        Self {
+            tt,
+            stop,
+            tablebase: None,
+            ordering,
+            config,
+            limits,
+            multipv: 1,
+            excluded: Vec::new(),
+            start,
+            nodes: 0,
+            next_check: 0,
+            stopped: false,
+            main: false,
+        }
+    }
+
+    fn iterate(
+        &mut self,
+        game: &mut Game,
+        first_depth: u8,
+        info: &mut dyn FnMut(&SearchResult),
+    ) -> SearchResult {
+        let max_depth = self.limits.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH);
+        let mut result = SearchResult {
+            best_move: None,
+            score: 0,
+            pv: Vec::new(),
+            depth: 0,
+            nodes: 0,
+            lines: Vec::new(),
+        };
+
+        for depth in first_depth..=max_depth {
+            let mut lines: Vec<Line> = Vec::new();
+            self.excluded.clear();
+
+            for index in 0..self.multipv {
+                let previous = result.lines.get(index).map_or(result.score, |l| l.score);
+                let mut pv = Vec::new();
+                let score = self.aspiration(game, depth, previous, &mut pv);
+
+                // Out of time, or every root move already has a line
+                if index > 0 && (self.stopped || pv.is_empty()) {
+                    break;
+                }
+
+                self.excluded.extend(pv.first());
+                lines.push(Line { score, pv });
+                if self.stopped {
+                    break;
+                }
+            }
+
+            // An unfinished iteration can't be trusted, unless there is nothing else
+            if self.stopped && result.best_move.is_some() {
+                break;
+            }
+
+            // Later lines can come out ahead when searched with a different window
+            lines.sort_by_key(|line| -line.score);
+            result.best_move = lines[0].pv.first().copied();
+            result.score = lines[0].score;
+            result.pv = lines[0].pv.clone();
+            result.depth = depth;
+            result.lines = lines;
+            let score = result.score;
+
+            result.nodes = self.nodes;
+            info(&result);
+
+            // No point going deeper once a forced mate has been found
+            if self.stopped || score.abs() >= MATE_BOUND {
+                break;
+            }
+        }
+
+        // Stopped before the first move was searched so play anything legal
+        if result.best_move.is_none() {
+            result.best_move = game.generate_moves().first().copied();
+        }
+
+        result.nodes = self.nodes;
+        result
+    }
+
+    // Search the root with a narrow window around the last score, widening it when the
+    // real score turns out to be outside
+    fn aspiration(&mut self, game: &mut Game, depth: u8, previous: i32, pv: &mut Vec<Move>) -> i32 {
+        if !self.config.aspiration || depth < 4 {
+            return self.negamax(game, depth, 0, -INFINITY, INFINITY, true, pv);
+        }
+
+        let mut window = ASPIRATION_WINDOW;
+        loop {
+            let alpha = (previous - window).max(-INFINITY);
+            let beta = (previous + window).min(INFINITY);
+            let score = self.negamax(game, depth, 0, alpha, beta, true, pv);
+
+            if self.stopped || (score > alpha && score < beta) {
+                return score;
+            }
+
+            // Give up on the window once it gets silly
+            if window > 1000 {
+                return self.negamax(game, depth, 0, -INFINITY, INFINITY, true, pv);
+            }
+            window *= 2;
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn negamax(
+        &mut self,
+        game: &mut Game,
+        mut depth: u8,
+        ply: u8,
+        mut alpha: i32,
+        beta: i32,
+        null_allowed: bool,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        if self.should_stop() {
+            return 0;
+        }
+
+        if let Some(score) = variant_score(game, ply) {
+            pv.clear();
+            return score;
+        }
+
+        let in_check = !game.checkers().is_empty();
+
+        // Don't drop into quiescence while in check, there may be no captures out of it
+        if in_check && self.config.check_extensions {
+            depth += 1;
+        }
+
+        if depth == 0 {
+            return self.quiescence(game, ply, alpha, beta);
+        }
+
+        if ply >= MAX_DEPTH {
+            return evaluate(game);
+        }
+
+        self.nodes += 1;
+
+        if ply > 0 && (game.state().halfmove_clock >= 100 || game.is_repetition()) {
+            return 0;
+        }
+
+        if ply > 0 {
+            if let Some(outcome) = self.probe(game) {
+                pv.clear();
+                return tablebase_score(outcome, ply);
+            }
+        }
+
+        let hash = game.hash();
+        let mut hash_move = None;
+        if let Some(entry) = self.tt.probe(hash) {
+            hash_move = entry.best_move;
+            let score = score_from_tt(entry.score, ply);
+
+            if ply > 0 && entry.depth >= depth {
+                let usable = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+
+                if usable {
+                    pv.clear();
+                    pv.extend(entry.best_move);
+                    return score.clamp(alpha, beta);
+                }
+            }
+        }
+
+        let static_eval = if in_check { -INFINITY } else { evaluate(game) };
+
+        // Passing is almost never better than moving, so if passing still beats beta the
+        // real search would too. Not safe with only pawns left where zugzwang is common
+        if self.config.null_move
+            && null_allowed
+            && ply > 0
+            && !in_check
+            && depth >= 3
+            && static_eval >= beta
+            && has_pieces(game)
+        {
+            let reduction = 2 + depth / 6;
+            let mut line = Vec::new();
+
+            game.make_null_move();
+            let score = -self.negamax(
+                game,
+                depth.saturating_sub(1 + reduction),
+                ply + 1,
+                -beta,
+                -beta + 1,
+                false,
+                &mut line,
+            );
+            game.unmake_null_move();
+
+            if self.stopped {
+                return 0;
+            }
+
+            // Mates found after passing aren't real
+            if score >= beta && score < MATE_BOUND {
+                return beta;
+            }
+        }
+
+        // Near the leaves quiet moves can't make up a big enough deficit
+        let futile = self.config.futility
+            && !in_check
+            && (depth as usize) < FUTILITY_MARGIN.len()
+            && alpha.abs() < MATE_BOUND
+            && static_eval + FUTILITY_MARGIN[depth as usize] <= alpha;
+
+        let mut moves = game.generate_moves();
+        if moves.is_empty() {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+
+        // The best move from an earlier search is most likely still the best
+        self.ordering.order(game, &mut moves, ply, hash_move);
+
+        let mut best_move = None;
+        let mut bound = Bound::Upper;
+        let mut line = Vec::new();
+        for (index, mv) in moves.into_iter().enumerate() {
+            if ply == 0 && self.excluded.contains(&mv) {
+                continue;
+            }
+
+            let quiet = !game.is_capture(mv) && mv.promotion.is_none();
+
+            game.make_move(mv);
+            let gives_check = !game.checkers().is_empty();
+
+            if futile && quiet && !gives_check && index > 0 {
+                game.unmake_move(mv);
+                continue;
+            }
+
+            // Moves ordered late are probably bad, check that with a shallower null window
+            // search first and only search properly if they turn out to be good
+            let reduce = self.config.late_move_reductions
+                && index >= LMR_FULL_MOVES
+                && depth >= 3
+                && quiet
+                && !in_check
+                && !gives_check;
+
+            let mut score = alpha + 1;
+            if reduce {
+                let reduction = if index >= 6 && depth >= 6 { 2 } else { 1 };
+                score = -self.negamax(
+                    game,
+                    depth - 1 - reduction,
+                    ply + 1,
+                    -alpha - 1,
+                    -alpha,
+                    true,
+                    &mut line,
+                );
+                line.clear();
+            }
+
+            if score > alpha {
+                score = -self.negamax(game, depth - 1, ply + 1, -beta, -alpha, true, &mut line);
+            }
+            game.unmake_move(mv);
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score >= beta {
+                self.ordering.cutoff(game, mv, ply, depth);
+                self.tt
+                    .store(hash, depth, score_to_tt(beta, ply), Bound::Lower, Some(mv));
+                return beta;
+            }
+
+            if score > alpha {
+                alpha = score;
+                best_move = Some(mv);
+                bound = Bound::Exact;
+                pv.clear();
+                pv.push(mv);
+                pv.append(&mut line);
+            }
+            line.clear();
+        }
+
+        // With moves left out the root result isn't the real one
+        if self.excluded.is_empty() || ply > 0 {
+            self.tt
+                .store(hash, depth, score_to_tt(alpha, ply), bound, best_move);
+        }
+        alpha
+    }
+
+    // Only search captures so the evaluation isn't taken in the middle of an exchange
+    fn quiescence(&mut self, game: &mut Game, ply: u8, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+
+        if let Some(score) = variant_score(game, ply) {
+            return score;
+        }
+
+        let stand_pat = evaluate(game);
+        if stand_pat >= beta || ply >= MAX_DEPTH {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut moves = game.generate_moves();
+        // Captures that lose material can't be what rescues the position
+        moves.retain(|&mv| mv.promotion.is_some() || (game.is_capture(mv) && game.see(mv) >= 0));
+        self.ordering.order(game, &mut moves, ply, None);
+
+        for mv in moves {
+            game.make_move(mv);
+            let score = -self.quiescence(game, ply + 1, -beta, -alpha);
+            game.unmake_move(mv);
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score >= beta {
+                return beta;
+            }
+            alpha = alpha.max(score);
+        }
+
+        alpha
+    }
+
+    // Tablebase result once few enough pieces are left
+    fn probe(&self, game: &Game) -> Option<Outcome> {
+        let tablebase = self.tablebase.filter(|_| game.is_standard())?;
+        let pieces = (game.color_bb(Color::White) | game.color_bb(Color::Black)).count();
+        if pieces as usize > tablebase.max_pieces() {
+            return None;
+        }
+        tablebase.outcome(game)
+    }
+
+    fn should_stop(&mut self) -> bool {
+        // Quiescence adds nodes without checking, so the count can step over an exact multiple
+        if self.nodes >= self.next_check {
+            self.next_check = self.nodes + CHECK_INTERVAL;
+            if let (true, Some(movetime)) = (self.main, self.limits.movetime) {
+                if self.start.elapsed() >= movetime {
+                    self.stop.store(true, Ordering::Relaxed);
+                }
+            }
+            self.stopped |= self.stop.load(Ordering::Relaxed);
+        }
+        self.stopped
+    }
+}
+
+// Mate score for a game won under the variant's rules, e.g. by three checks
+fn variant_score(game: &Game, ply: u8) -> Option<i32> {
+    let winner = game.winner()?;
+    Some(if winner == game.turn() {
+        MATE - ply as i32
+    } else {
+        -MATE + ply as i32
+    })
+}
+
+// Score of a tablebase result found at a ply, mates count from the root like searched ones
+fn tablebase_score(outcome: Outcome, ply: u8) -> i32 {
+    match outcome {
+        Outcome::Win(plies) => MATE - ply as i32 - plies as i32,
+        Outcome::Loss(plies) => -MATE + ply as i32 + plies as i32,
+        Outcome::Draw => 0,
+    }
+}
+
+// Play the tablebase move at the root, following the tables for the principal variation
+fn tablebase_root(tablebase: &Tablebase, game: &mut Game) -> Option<SearchResult> {
+    let (best, outcome) = tablebase.best_move(game)?;
+    let score = tablebase_score(outcome, 0);
+
+    let mut pv = vec![best];
+    game.make_move(best);
+    while pv.len() < MAX_DEPTH as usize && !game.is_repetition() {
+        let Some((mv, _)) = tablebase.best_move(game) else {
+            break;
+        };
+        pv.push(mv);
+        game.make_move(mv);
+    }
+    for &mv in pv.iter().rev() {
+        game.unmake_move(mv);
+    }
+
+    Some(SearchResult {
+        best_move: Some(best),
+        score,
+        pv: pv.clone(),
+        depth: 0,
+        nodes: 0,
+        lines: vec![Line { score, pv }],
+    })
+}
+
+// Whether the side to move has anything besides pawns and the king
+fn has_pieces(game: &Game) -> bool {
+    let side = game.turn();
+    [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+        .into_iter()
+        .any(|piece| !game.piece_bb(side, piece).is_empty())
+}
+
+// Mate scores are stored relative to the node so they stay right when found from another ply
+fn score_to_tt(score: i32, ply: u8) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply as i32
+    } else if score <= -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: u8) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply as i32
+    } else if score <= -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{
+    board::BitBoard,
+    game::{Game, LegalMove, Move},
+    piece::{Color, Piece},
+};
+
+// Rough values for trading pieces, the king is worth more than anything it can win
+pub const SEE_VALUES: [i32; Piece::COUNT] = [100, 500, 300, 300, 900, 20_000];
+
+// Order attackers are used in, cheapest first
+const CHEAPEST_FIRST: [Piece; Piece::COUNT] = [
+    Piece::Pawn,
+    Piece::Knight,
+    Piece::Bishop,
+    Piece::Rook,
+    Piece::Queen,
+    Piece::King,
+];
+
+fn value(piece: Piece) -> i32 {
+    SEE_VALUES[piece as usize]
+}
+
+impl Game {
+    /// Static exchange evaluation, the material a move wins once every capture on its target
+    /// square has been played out with the cheapest piece each time. Either side can stop
+    /// capturing when carrying on would lose material
+    pub fn see(&self, mv: Move) -> i32 {
+        let Some((mut side, piece)) = self.piece_at(mv.from) else {
+            return 0;
+        };
+        let to = mv.to;
+        let mut occupied = (self.color_bb(Color::White) | self.color_bb(Color::Black))
+            ^ BitBoard::from_square(mv.from);
+
+        // gain[n] is what the side making capture n has won if the exchange stops there
+        let mut gain = [0; 32];
+        gain[0] = match self.piece_at(to) {
+            Some((_, captured)) => value(captured),
+            None => 0,
+        };
+
+        if mv.kind == LegalMove::EnPassant {
+            let victim = to.offset(0, -side.forward()).unwrap();
+            occupied ^= BitBoard::from_square(victim);
+            gain[0] = value(Piece::Pawn);
+        }
+
+        let mut on_square = match mv.promotion {
+            Some(promotion) => {
+                gain[0] += value(promotion) - value(Piece::Pawn);
+                value(promotion)
+            }
+            None => value(piece),
+        };
+
+        let mut depth = 0;
+        loop {
+            side = !side;
+
+            // Recalculated each time so sliders behind a piece that just captured join in
+            let attackers = (self.attackers_to_occupied(to, Color::White, occupied)
+                | self.attackers_to_occupied(to, Color::Black, occupied))
+                & occupied;
+            let ours = attackers & self.color_bb(side);
+
+            let Some((piece, from)) = CHEAPEST_FIRST.into_iter().find_map(|piece| {
+                (ours & self.piece_bb(side, piece))
+                    .lsb()
+                    .map(|sqr| (piece, sqr))
+            }) else {
+                break;
+            };
+
+            // The king can't take if the square is still defended
+            if piece == Piece::King && !(attackers & self.color_bb(!side)).is_empty() {
+                break;
+            }
+
+            depth += 1;
+            gain[depth] = on_square - gain[depth - 1];
+            if depth == gain.len() - 1 {
+                break;
+            }
+
+            on_square = value(piece);
+            occupied ^= BitBoard::from_square(from);
+        }
+
+        // Work back up the sequence, each side only captures if it gains from doing so
+        while depth > 0 {
+            depth -= 1;
+            gain[depth] = -(-gain[depth]).max(gain[depth + 1]);
+        }
+
+        gain[0]
+    }
+}
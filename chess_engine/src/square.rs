@@ -0,0 +1,105 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// One of the 64 squares, a1 is 0 and h8 is 63 so it matches the bit in a BitBoard
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Square(u8);
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseSquareError;
+
+impl Display for ParseSquareError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Squares are a file a-h followed by a rank 1-8")
+    }
+}
+
+impl std::error::Error for ParseSquareError {}
+
+impl Square {
+    pub const ALL: [Square; 64] = {
+        let mut all = [Square(0); 64];
+        let mut i = 0;
+        while i < 64 {
+            all[i] = Square(i as u8);
+            i += 1;
+        }
+        all
+    };
+
+    pub const A1: Square = Square(0);
+    pub const C1: Square = Square(2);
+    pub const D1: Square = Square(3);
+    pub const E1: Square = Square(4);
+    pub const F1: Square = Square(5);
+    pub const G1: Square = Square(6);
+    pub const H1: Square = Square(7);
+    pub const A8: Square = Square(56);
+    pub const C8: Square = Square(58);
+    pub const D8: Square = Square(59);
+    pub const E8: Square = Square(60);
+    pub const F8: Square = Square(61);
+    pub const G8: Square = Square(62);
+    pub const H8: Square = Square(63);
+
+    /// None if the file or rank is off the board
+    pub fn new(file: u8, rank: u8) -> Option<Self> {
+        if file < 8 && rank < 8 {
+            Some(Self(rank * 8 + file))
+        } else {
+            None
+        }
+    }
+
+    pub fn from_index(index: usize) -> Option<Self> {
+        Self::ALL.get(index).copied()
+    }
+
+    /// Position of the square's bit in a bitboard
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn file(self) -> u8 {
+        self.0 % 8
+    }
+
+    pub fn rank(self) -> u8 {
+        self.0 / 8
+    }
+
+    /// Square a number of files and ranks away, None if that is off the board
+    pub fn offset(self, files: i8, ranks: i8) -> Option<Self> {
+        let file = self.file().checked_add_signed(files)?;
+        let rank = self.rank().checked_add_signed(ranks)?;
+        Self::new(file, rank)
+    }
+
+    /// Same square seen from the other side of the board
+    pub fn flip_rank(self) -> Self {
+        Self(self.0 ^ 56)
+    }
+}
+
+impl FromStr for Square {
+    type Err = ParseSquareError;
+
+    /// Algebraic notation e.g. e4
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Ok(Self((rank - b'1') * 8 + file - b'a')),
+            _ => Err(ParseSquareError),
+        }
+    }
+}
+
+impl Display for Square {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            (b'a' + self.file()) as char,
+            (b'1' + self.rank()) as char
+        )
+    }
+}
@@ -1,11 +1,57 @@
+use crate::{
+    board::BitBoard,
+    piece::{Color, Piece},
+    square::Square,
+};
+
+// Irreversible part of a position, saved before every move so it can be undone
+#[derive(Clone, Copy, Debug)]
 pub struct State {
-    castling_rights: CastlingRights,
+    pub castling_rights: CastlingRights,
+    pub en_passant: Option<Square>,
+    // Half moves since the last capture or pawn move, for the fifty move rule
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    // Piece type captured by the move that led to this state
+    pub captured: Option<Piece>,
+    // Zobrist key of the position, restored along with everything else on unmake
+    pub hash: u64,
+    // Enemy pieces giving check to the side to move
+    pub checkers: BitBoard,
+    // Checks each side has given, only counted in Three-check
+    pub checks: [u8; Color::COUNT],
+    // Pieces the variant took off the board after this move, e.g. in an Atomic explosion
+    pub removed: u8,
+    // Captured pieces each side can drop back on the board, counted by piece type. Kings
+    // never go in so there is no slot for them
+    pub pockets: [[u8; Piece::COUNT - 1]; Color::COUNT],
+    // Pieces that started out as pawns, they go back to being pawns when captured
+    pub promoted: BitBoard,
+    // Whether the piece captured by the move that led to this state had been promoted
+    pub captured_promoted: bool,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl State {
     pub fn new() -> Self {
         State {
             castling_rights: CastlingRights::new(),
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            captured: None,
+            hash: 0,
+            checkers: BitBoard(0),
+            checks: [0; Color::COUNT],
+            removed: 0,
+            pockets: [[0; Piece::COUNT - 1]; Color::COUNT],
+            promoted: BitBoard(0),
+            captured_promoted: false,
         }
     }
 }
@@ -17,10 +63,58 @@ impl State {
 // 1 Black King
 // 2 White Queen
 // 3 White King
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct CastlingRights(u8);
 
+impl Default for CastlingRights {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CastlingRights {
+    pub const BLACK_QUEEN: u8 = 1 << 0;
+    pub const BLACK_KING: u8 = 1 << 1;
+    pub const WHITE_QUEEN: u8 = 1 << 2;
+    pub const WHITE_KING: u8 = 1 << 3;
+
     pub fn new() -> Self {
         CastlingRights(0)
     }
+
+    pub fn all() -> Self {
+        CastlingRights(0b1111)
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub fn has(&self, right: u8) -> bool {
+        self.0 & right != 0
+    }
+
+    pub fn add(&mut self, right: u8) {
+        self.0 |= right;
+    }
+
+    pub fn remove(&mut self, right: u8) {
+        self.0 &= !right;
+    }
+
+    pub fn kingside(side: Color) -> u8 {
+        if side == Color::White {
+            Self::WHITE_KING
+        } else {
+            Self::BLACK_KING
+        }
+    }
+
+    pub fn queenside(side: Color) -> u8 {
+        if side == Color::White {
+            Self::WHITE_QUEEN
+        } else {
+            Self::BLACK_QUEEN
+        }
+    }
 }
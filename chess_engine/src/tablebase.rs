@@ -0,0 +1,1234 @@
+//! Endgame tables giving distance to mate for every position with a few pieces.
+//!
+//! Each material is saved to its own file, e.g. KQK.tb. Positions are stored in index
+//! order, the order the table is generated in, as a value each: 0 for a draw, 255 for a
+//! position that can't happen or is stored under a mirror image, and anything else plies
+//! to mate plus one, odd for the side to move being mated and even for it mating.
+//!
+//! Long runs of draws and unused positions make up most of a table, and mates of the same
+//! length cluster, so the values are compressed in two steps. First runs of equal values
+//! become a value byte then the length of the run. Those bytes are then Huffman coded,
+//! common bytes taking only a few bits. A file is laid out as
+//!
+//! - the magic bytes `CETB` and a version byte, currently 2
+//! - the length of the material name in one byte, then the name
+//! - how many bytes the runs take
+//! - the Huffman code length in bits of each of the 256 byte values, 0 if it isn't used
+//! - the coded run bytes, most significant bit first and padded with zeros to a byte
+//!
+//! Numbers, the run lengths and how many bytes they take, are LEB128 varints: seven bits
+//! a byte lowest first, with the top bit set on every byte but the last. The Huffman codes
+//! are canonical, given out in order of length then byte value, so the lengths are all a
+//! reader needs to rebuild them.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fmt::Display,
+    fs, io,
+    path::Path,
+};
+
+use crate::{
+    attacks,
+    board::BitBoard,
+    game::{Game, Move},
+    piece::{Color, Piece},
+    square::Square,
+};
+
+/// Most pieces a table can cover, kings included
+pub const MAX_PIECES: usize = 4;
+
+/// Extension of table files, the file name is the material e.g. KQK.tb
+pub const EXTENSION: &str = "tb";
+
+const MAGIC: &[u8; 4] = b"CETB";
+const VERSION: u8 = 2;
+
+// Values in a table while it is being generated. Anything below DRAW is plies to mate
+// plus one, even plies for the side to move being mated and odd for it mating
+const UNKNOWN: u8 = 0;
+const DRAW: u8 = 254;
+// Squares that clash, a pawn on the end rank, the side that just moved in check, or a
+// position stored under another of its mirror images
+const UNUSED: u8 = 255;
+
+// Order the pieces of each side are listed in, in names and in tables
+const NAME_ORDER: [Piece; 5] = [
+    Piece::Queen,
+    Piece::Rook,
+    Piece::Bishop,
+    Piece::Knight,
+    Piece::Pawn,
+];
+
+// Without pawns the board can be turned and mirrored so the white king is always in the
+// a1-d1-d4 triangle. Pawns only move one way so then it is just kept on the a-d files
+// a1 b1 c1 d1 b2 c2 d2 c3 d3 d4
+const TRIANGLE: [usize; 10] = [0, 1, 2, 3, 9, 10, 11, 18, 19, 27];
+
+/// Result of a position with perfect play, from the side to move's point of view. The
+/// number is how many plies the mate takes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Win(u32),
+    Loss(u32),
+    Draw,
+}
+
+impl Outcome {
+    fn from_value(value: u8) -> Self {
+        match value {
+            UNKNOWN | DRAW | UNUSED => Outcome::Draw,
+            value if (value - 1) % 2 == 0 => Outcome::Loss(value as u32 - 1),
+            value => Outcome::Win(value as u32 - 1),
+        }
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Win(plies) => write!(f, "mate in {}", plies.div_ceil(2)),
+            Outcome::Loss(0) => write!(f, "checkmated"),
+            Outcome::Loss(plies) => write!(f, "mated in {}", plies / 2),
+            Outcome::Draw => write!(f, "draw"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TablebaseError {
+    InvalidMaterial(String),
+    TooManyPieces(String),
+    InvalidFile(String),
+    Io(io::Error),
+}
+
+impl Display for TablebaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TablebaseError::InvalidMaterial(name) => write!(f, "'{name}' is not a material"),
+            TablebaseError::TooManyPieces(name) => {
+                write!(f, "{name} has more than {MAX_PIECES} pieces")
+            }
+            TablebaseError::InvalidFile(name) => write!(f, "{name} is not a tablebase file"),
+            TablebaseError::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for TablebaseError {}
+
+impl From<io::Error> for TablebaseError {
+    fn from(err: io::Error) -> Self {
+        TablebaseError::Io(err)
+    }
+}
+
+/// The pieces each side has besides its king e.g. KRKP is a rook against a pawn
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Material {
+    pieces: [Vec<Piece>; Color::COUNT],
+}
+
+impl Material {
+    fn new(mut white: Vec<Piece>, mut black: Vec<Piece>) -> Self {
+        let order = |piece: &Piece| NAME_ORDER.iter().position(|p| p == piece);
+        white.sort_by_key(order);
+        black.sort_by_key(order);
+        Self {
+            pieces: [white, black],
+        }
+    }
+
+    /// Parse a name like KQK or KRKP, white's pieces come first
+    pub fn parse(name: &str) -> Result<Self, TablebaseError> {
+        let invalid = || TablebaseError::InvalidMaterial(name.to_string());
+        let rest = name.strip_prefix('K').ok_or_else(invalid)?;
+        let (white, black) = rest.split_once('K').ok_or_else(invalid)?;
+
+        let parse_side = |letters: &str| -> Result<Vec<Piece>, TablebaseError> {
+            letters
+                .chars()
+                .map(|chr| match Piece::from_char(chr) {
+                    Some(piece) if chr.is_ascii_uppercase() && piece != Piece::King => Ok(piece),
+                    _ => Err(invalid()),
+                })
+                .collect()
+        };
+
+        let material = Self::new(parse_side(white)?, parse_side(black)?);
+        if material.count() > MAX_PIECES {
+            return Err(TablebaseError::TooManyPieces(name.to_string()));
+        }
+        Ok(material)
+    }
+
+    fn from_game(game: &Game) -> Self {
+        let side = |color| {
+            NAME_ORDER
+                .iter()
+                .flat_map(|&piece| {
+                    std::iter::repeat_n(piece, game.piece_bb(color, piece).count() as usize)
+                })
+                .collect()
+        };
+        Self::new(side(Color::White), side(Color::Black))
+    }
+
+    /// Number of pieces, kings included
+    pub fn count(&self) -> usize {
+        2 + self.pieces[0].len() + self.pieces[1].len()
+    }
+
+    fn has_pawns(&self) -> bool {
+        self.pieces
+            .iter()
+            .flatten()
+            .any(|&piece| piece == Piece::Pawn)
+    }
+
+    fn flipped(&self) -> Self {
+        Self::new(self.pieces[1].clone(), self.pieces[0].clone())
+    }
+
+    // Tables are only made with the stronger side as white, the other way round is the
+    // same table with the colours swapped
+    fn is_canonical(&self) -> bool {
+        let strength = |pieces: &Vec<Piece>| {
+            let value: u32 = pieces.iter().map(|&piece| piece_value(piece)).sum();
+            let order: Vec<usize> = pieces
+                .iter()
+                .map(|piece| 5 - NAME_ORDER.iter().position(|p| p == piece).unwrap())
+                .collect();
+            (value, order)
+        };
+        strength(&self.pieces[0]) >= strength(&self.pieces[1])
+    }
+
+    // Every piece in table order: the white king, white's pieces, the black king, black's
+    fn layout(&self) -> Vec<(Color, Piece)> {
+        let mut layout = Vec::new();
+        for color in Color::ALL {
+            layout.push((color, Piece::King));
+            layout.extend(
+                self.pieces[color as usize]
+                    .iter()
+                    .map(|&piece| (color, piece)),
+            );
+        }
+        layout
+    }
+
+    // Materials reachable by a capture or a promotion, which have to be solved first
+    fn successors(&self) -> Vec<Material> {
+        let mut next = Vec::new();
+
+        for color in Color::ALL {
+            let own = &self.pieces[color as usize];
+            let other = &self.pieces[!color as usize];
+
+            for (i, &piece) in own.iter().enumerate() {
+                let mut without = own.clone();
+                without.remove(i);
+
+                let captured = match color {
+                    Color::White => Material::new(without.clone(), other.clone()),
+                    Color::Black => Material::new(other.clone(), without.clone()),
+                };
+                next.push(captured);
+
+                if piece == Piece::Pawn {
+                    for promotion in Piece::PROMOTIONS {
+                        let mut promoted = without.clone();
+                        promoted.push(promotion);
+                        next.push(match color {
+                            Color::White => Material::new(promoted, other.clone()),
+                            Color::Black => Material::new(other.clone(), promoted),
+                        });
+                    }
+                }
+            }
+        }
+
+        next.retain(|material| material.count() > 2);
+        next
+    }
+}
+
+impl Display for Material {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for pieces in &self.pieces {
+            write!(f, "K")?;
+            for piece in pieces {
+                write!(f, "{}", piece.to_char().to_ascii_uppercase())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn piece_value(piece: Piece) -> u32 {
+    match piece {
+        Piece::Pawn => 1,
+        Piece::Knight | Piece::Bishop => 3,
+        Piece::Rook => 5,
+        Piece::Queen => 9,
+        Piece::King => 0,
+    }
+}
+
+// One of the eight ways of turning and mirroring the board: bit 0 mirrors the files,
+// bit 1 the ranks and bit 2 swaps files with ranks
+fn transform(sqr: Square, symmetry: u8) -> Square {
+    let (mut file, mut rank) = (sqr.file(), sqr.rank());
+    if symmetry & 4 != 0 {
+        (file, rank) = (rank, file);
+    }
+    if symmetry & 1 != 0 {
+        file = 7 - file;
+    }
+    if symmetry & 2 != 0 {
+        rank = 7 - rank;
+    }
+    Square::new(file, rank).unwrap()
+}
+
+// A position in a table, squares are in the order of the table's layout
+#[derive(Clone, Copy)]
+struct Position {
+    squares: [Square; MAX_PIECES],
+    side: Color,
+}
+
+// A legal move in a table position
+struct TableMove {
+    piece: usize,
+    to: Square,
+    captured: Option<usize>,
+    promotion: Option<Piece>,
+}
+
+/// Distance to mate for every position with one set of material
+pub struct Table {
+    material: Material,
+    layout: Vec<(Color, Piece)>,
+    pawns: bool,
+    // One byte per position, see UNKNOWN
+    data: Vec<u8>,
+}
+
+impl Table {
+    fn empty(material: Material) -> Self {
+        let layout = material.layout();
+        let pawns = material.has_pawns();
+        let mut table = Self {
+            material,
+            layout,
+            pawns,
+            data: Vec::new(),
+        };
+        table.data = vec![UNKNOWN; table.size()];
+        table
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn king_slots(&self) -> usize {
+        if self.pawns {
+            32
+        } else {
+            TRIANGLE.len()
+        }
+    }
+
+    // Positions for each side to move
+    fn half(&self) -> usize {
+        self.king_slots() * 64usize.pow(self.layout.len() as u32 - 1)
+    }
+
+    fn size(&self) -> usize {
+        2 * self.half()
+    }
+
+    fn king_slot(&self, sqr: Square) -> Option<usize> {
+        if self.pawns {
+            (sqr.file() < 4).then(|| (sqr.rank() * 4 + sqr.file()) as usize)
+        } else {
+            TRIANGLE.iter().position(|&s| s == sqr.index())
+        }
+    }
+
+    // Index of a position under whichever of its mirror images is stored
+    fn index(&self, pos: &Position) -> usize {
+        let n = self.layout.len();
+        let symmetries = if self.pawns { 0..2 } else { 0..8 };
+        let mut best = usize::MAX;
+
+        for symmetry in symmetries {
+            let Some(slot) = self.king_slot(transform(pos.squares[0], symmetry)) else {
+                continue;
+            };
+            let rest = pos.squares[1..n].iter().fold(0, |index, &sqr| {
+                index * 64 + transform(sqr, symmetry).index()
+            });
+            best = best.min(slot * 64usize.pow(n as u32 - 1) + rest);
+        }
+
+        pos.side as usize * self.half() + best
+    }
+
+    fn position(&self, index: usize) -> Position {
+        let n = self.layout.len();
+        let side = if index < self.half() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let mut rest = index % self.half();
+        let mut squares = [Square::A1; MAX_PIECES];
+
+        for i in (1..n).rev() {
+            squares[i] = Square::from_index(rest % 64).unwrap();
+            rest /= 64;
+        }
+        squares[0] = if self.pawns {
+            Square::new(rest as u8 % 4, rest as u8 / 4).unwrap()
+        } else {
+            Square::ALL[TRIANGLE[rest]]
+        };
+
+        Position { squares, side }
+    }
+
+    fn occupied(&self, pos: &Position, captured: Option<usize>) -> BitBoard {
+        let mut occupied = BitBoard(0);
+        for i in 0..self.layout.len() {
+            if Some(i) != captured {
+                occupied |= BitBoard::from_square(pos.squares[i]);
+            }
+        }
+        occupied
+    }
+
+    // Squares piece i attacks, pawn pushes aren't attacks
+    fn attacks(&self, pos: &Position, i: usize, occupied: BitBoard) -> BitBoard {
+        let (color, piece) = self.layout[i];
+        let sqr = pos.squares[i];
+        match piece {
+            Piece::Pawn => attacks::pawn_attacks(sqr, color),
+            Piece::Knight => attacks::knight_attacks(sqr),
+            Piece::Bishop => attacks::bishop_attacks(sqr, occupied),
+            Piece::Rook => attacks::rook_attacks(sqr, occupied),
+            Piece::Queen => attacks::queen_attacks(sqr, occupied),
+            Piece::King => attacks::king_attacks(sqr),
+        }
+    }
+
+    // Whether a side's king is attacked, ignoring a piece that has just been captured
+    fn in_check(&self, pos: &Position, side: Color, captured: Option<usize>) -> bool {
+        let king = self.king(side);
+        let target = BitBoard::from_square(pos.squares[king]);
+        let occupied = self.occupied(pos, captured);
+
+        (0..self.layout.len()).any(|i| {
+            self.layout[i].0 != side
+                && Some(i) != captured
+                && !(self.attacks(pos, i, occupied) & target).is_empty()
+        })
+    }
+
+    fn king(&self, side: Color) -> usize {
+        match side {
+            Color::White => 0,
+            Color::Black => 1 + self.material.pieces[0].len(),
+        }
+    }
+
+    fn is_valid(&self, pos: &Position) -> bool {
+        let n = self.layout.len();
+        if self.occupied(pos, None).count() as usize != n {
+            return false;
+        }
+
+        let pawn_on_end = (0..n)
+            .any(|i| self.layout[i].1 == Piece::Pawn && matches!(pos.squares[i].rank(), 0 | 7));
+        !pawn_on_end && !self.in_check(pos, !pos.side, None)
+    }
+
+    fn piece_on(&self, pos: &Position, sqr: Square) -> Option<usize> {
+        (0..self.layout.len()).find(|&i| pos.squares[i] == sqr)
+    }
+
+    fn legal_moves(&self, pos: &Position) -> Vec<TableMove> {
+        let side = pos.side;
+        let occupied = self.occupied(pos, None);
+        let mut own = BitBoard(0);
+        for i in 0..self.layout.len() {
+            if self.layout[i].0 == side {
+                own |= BitBoard::from_square(pos.squares[i]);
+            }
+        }
+        let mut moves = Vec::new();
+
+        for i in 0..self.layout.len() {
+            let (color, piece) = self.layout[i];
+            if color != side {
+                continue;
+            }
+            let from = pos.squares[i];
+
+            let targets = if piece == Piece::Pawn {
+                let enemies = occupied & !own;
+                let mut targets = attacks::pawn_attacks(from, side) & enemies;
+                if let Some(one) = from.offset(0, side.forward()) {
+                    if (occupied & BitBoard::from_square(one)).is_empty() {
+                        targets |= BitBoard::from_square(one);
+
+                        let start = if side == Color::White { 1 } else { 6 };
+                        if let Some(two) = one.offset(0, side.forward()) {
+                            if from.rank() == start
+                                && (occupied & BitBoard::from_square(two)).is_empty()
+                            {
+                                targets |= BitBoard::from_square(two);
+                            }
+                        }
+                    }
+                }
+                targets
+            } else {
+                self.attacks(pos, i, occupied) & !own
+            };
+
+            for to in targets {
+                let captured = self.piece_on(pos, to);
+                let mut after = *pos;
+                after.squares[i] = to;
+                if self.in_check(&after, side, captured) {
+                    continue;
+                }
+
+                if piece == Piece::Pawn && matches!(to.rank(), 0 | 7) {
+                    for promotion in Piece::PROMOTIONS {
+                        moves.push(TableMove {
+                            piece: i,
+                            to,
+                            captured,
+                            promotion: Some(promotion),
+                        });
+                    }
+                } else {
+                    moves.push(TableMove {
+                        piece: i,
+                        to,
+                        captured,
+                        promotion: None,
+                    });
+                }
+            }
+        }
+
+        moves
+    }
+
+    // Positions that could have led to this one by a move that stays in this table
+    fn predecessors(&self, pos: &Position) -> Vec<Position> {
+        let mover = !pos.side;
+        let occupied = self.occupied(pos, None);
+        let mut found = Vec::new();
+
+        for i in 0..self.layout.len() {
+            let (color, piece) = self.layout[i];
+            if color != mover {
+                continue;
+            }
+            let to = pos.squares[i];
+
+            let froms = if piece == Piece::Pawn {
+                let mut froms = BitBoard(0);
+                let back = -mover.forward();
+                if let Some(one) = to.offset(0, back) {
+                    if (occupied & BitBoard::from_square(one)).is_empty() {
+                        if !matches!(one.rank(), 0 | 7) {
+                            froms |= BitBoard::from_square(one);
+                        }
+
+                        let double_to = if mover == Color::White { 3 } else { 4 };
+                        if let Some(two) = one.offset(0, back) {
+                            if to.rank() == double_to
+                                && (occupied & BitBoard::from_square(two)).is_empty()
+                            {
+                                froms |= BitBoard::from_square(two);
+                            }
+                        }
+                    }
+                }
+                froms
+            } else {
+                // Pieces other than pawns move the same way back as forward
+                self.attacks(pos, i, occupied) & !occupied
+            };
+
+            for from in froms {
+                let mut before = *pos;
+                before.squares[i] = from;
+                before.side = mover;
+                if !self.in_check(&before, pos.side, None) {
+                    found.push(before);
+                }
+            }
+        }
+
+        found
+    }
+}
+
+// Where the pieces of a position go in the table it moves into after a capture or
+// promotion, worked out once instead of for every move
+struct Link<'a> {
+    // None when only the kings are left
+    table: Option<&'a Table>,
+    // The table has the colours the other way round
+    flip: bool,
+    // Which of the original pieces goes in each place of the table's layout
+    slots: [usize; MAX_PIECES],
+    captured: Option<usize>,
+    // The pawn promoting and what to
+    promotion: Option<(usize, Piece)>,
+}
+
+impl Link<'_> {
+    fn read(&self, side: Color, square: impl Fn(usize) -> Square) -> u8 {
+        let Some(table) = self.table else {
+            return DRAW;
+        };
+
+        let mut pos = Position {
+            squares: [Square::A1; MAX_PIECES],
+            side: if self.flip { !side } else { side },
+        };
+        for slot in 0..table.layout.len() {
+            let sqr = square(self.slots[slot]);
+            pos.squares[slot] = if self.flip { sqr.flip_rank() } else { sqr };
+        }
+        table.data[table.index(&pos)]
+    }
+}
+
+/// Distance to mate tables for small endings, built by retrograde analysis
+#[derive(Default)]
+pub struct Tablebase {
+    tables: HashMap<Material, Table>,
+    max_pieces: usize,
+}
+
+impl Tablebase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names of the tables loaded or generated
+    pub fn materials(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.tables.keys().map(|m| m.to_string()).collect();
+        names.sort();
+        names
+    }
+
+    /// Most pieces in any table, positions with more can't be probed
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    fn insert(&mut self, table: Table) {
+        self.max_pieces = self.max_pieces.max(table.material.count());
+        self.tables.insert(table.material.clone(), table);
+    }
+
+    /// Generate the table for a material, e.g. KQK, and any it needs that aren't there
+    /// yet. The mirror image, e.g. KKQ, is covered by the same table
+    pub fn generate(&mut self, name: &str) -> Result<(), TablebaseError> {
+        let material = Material::parse(name)?;
+        self.generate_material(if material.is_canonical() {
+            material
+        } else {
+            material.flipped()
+        });
+        Ok(())
+    }
+
+    fn generate_material(&mut self, material: Material) {
+        if self.tables.contains_key(&material) {
+            return;
+        }
+
+        for next in material.successors() {
+            let next = if next.is_canonical() {
+                next
+            } else {
+                next.flipped()
+            };
+            self.generate_material(next);
+        }
+
+        let table = self.solve(Table::empty(material));
+        self.insert(table);
+    }
+
+    // Retrograde analysis. Mates are found first, then the positions a move before those
+    // are wins, and positions where every move leads to a win for the other side are
+    // losses, one ply further out at a time. Whatever is left at the end is a draw
+    fn solve(&self, mut table: Table) -> Table {
+        let links = self.links(&table);
+        // Positions settled at each ply, and positions to look at again once a ply is done
+        let mut settled: Vec<Vec<usize>> = vec![Vec::new()];
+        let mut recheck: Vec<Vec<usize>> = vec![Vec::new()];
+        let schedule = |list: &mut Vec<Vec<usize>>, ply: usize, index: usize| {
+            if list.len() <= ply {
+                list.resize(ply + 1, Vec::new());
+            }
+            list[ply].push(index);
+        };
+
+        for index in 0..table.size() {
+            let pos = table.position(index);
+            if !table.is_valid(&pos) || table.index(&pos) != index {
+                table.data[index] = UNUSED;
+            }
+        }
+
+        for index in 0..table.size() {
+            if table.data[index] == UNUSED {
+                continue;
+            }
+            let pos = table.position(index);
+
+            if table.legal_moves(&pos).is_empty() {
+                if table.in_check(&pos, pos.side, None) {
+                    table.data[index] = 1;
+                    settled[0].push(index);
+                } else {
+                    table.data[index] = DRAW;
+                }
+            } else if let Some(plies) = self.resolve(&table, &links, &pos) {
+                // A capture or promotion into a table that is already solved
+                schedule(&mut recheck, plies - 1, index);
+            }
+        }
+
+        let mut ply = 0;
+        while ply < settled.len() || ply < recheck.len() {
+            let done = settled.get(ply).cloned().unwrap_or_default();
+            for index in done {
+                for before in table.predecessors(&table.position(index)) {
+                    let before = table.index(&before);
+                    if table.data[before] == UNKNOWN {
+                        schedule(&mut recheck, ply, before);
+                    }
+                }
+            }
+
+            let candidates = recheck.get(ply).cloned().unwrap_or_default();
+            for index in candidates {
+                if table.data[index] != UNKNOWN {
+                    continue;
+                }
+                let Some(plies) = self.resolve(&table, &links, &table.position(index)) else {
+                    continue;
+                };
+
+                if plies == ply + 1 {
+                    assert!(plies < DRAW as usize - 1, "Mate too long to store");
+                    table.data[index] = plies as u8 + 1;
+                    schedule(&mut settled, plies, index);
+                } else if plies > ply + 1 {
+                    schedule(&mut recheck, plies - 1, index);
+                }
+            }
+
+            ply += 1;
+        }
+
+        for value in &mut table.data {
+            if *value == UNKNOWN || *value == UNUSED {
+                *value = DRAW;
+            }
+        }
+        table
+    }
+
+    // Plies to mate if the position is decided by what is known about the positions after
+    // each move, None if it can't be told yet
+    fn resolve(&self, table: &Table, links: &[Link], pos: &Position) -> Option<usize> {
+        let mut win: Option<usize> = None;
+        let mut loss = Some(0);
+
+        for mv in table.legal_moves(pos) {
+            let value = if mv.captured.is_none() && mv.promotion.is_none() {
+                let mut after = *pos;
+                after.squares[mv.piece] = mv.to;
+                after.side = !pos.side;
+                table.data[table.index(&after)]
+            } else {
+                // A capture or promotion moves into another table
+                let promotion = mv.promotion.map(|piece| (mv.piece, piece));
+                let link = links
+                    .iter()
+                    .find(|link| link.captured == mv.captured && link.promotion == promotion)
+                    .expect("Every capture and promotion is linked");
+                link.read(!pos.side, |i| {
+                    if i == mv.piece {
+                        mv.to
+                    } else {
+                        pos.squares[i]
+                    }
+                })
+            };
+
+            match value {
+                UNKNOWN | UNUSED | DRAW => loss = None,
+                value => {
+                    let plies = value as usize - 1;
+                    if plies.is_multiple_of(2) {
+                        win = Some(win.map_or(plies + 1, |w| w.min(plies + 1)));
+                    } else {
+                        loss = loss.map(|l: usize| l.max(plies + 1));
+                    }
+                }
+            }
+        }
+
+        win.or(loss)
+    }
+
+    // Links from a table to the tables reached by each capture and promotion
+    fn links(&self, table: &Table) -> Vec<Link<'_>> {
+        let n = table.layout.len();
+        let mut links = Vec::new();
+
+        let captures = (0..n)
+            .filter(|&i| table.layout[i].1 != Piece::King)
+            .map(Some);
+        for captured in std::iter::once(None).chain(captures) {
+            let pawns = (0..n).filter(|&i| table.layout[i].1 == Piece::Pawn);
+            let promotions = pawns.flat_map(|i| {
+                Piece::PROMOTIONS
+                    .into_iter()
+                    .map(move |piece| Some((i, piece)))
+            });
+
+            for promotion in std::iter::once(None).chain(promotions) {
+                if (captured.is_none() && promotion.is_none())
+                    || promotion.is_some_and(|(i, _)| Some(i) == captured)
+                {
+                    continue;
+                }
+
+                let pieces: Vec<(usize, Color, Piece)> = (0..n)
+                    .filter(|&i| Some(i) != captured)
+                    .map(|i| {
+                        let (color, piece) = table.layout[i];
+                        match promotion {
+                            Some((pawn, promoted)) if pawn == i => (i, color, promoted),
+                            _ => (i, color, piece),
+                        }
+                    })
+                    .collect();
+
+                if let Some(mut link) = self.link(&pieces) {
+                    link.captured = captured;
+                    link.promotion = promotion;
+                    links.push(link);
+                }
+            }
+        }
+
+        links
+    }
+
+    // Find the table for a set of pieces, given as the index each is known by, and where
+    // each goes in that table
+    fn link(&self, pieces: &[(usize, Color, Piece)]) -> Option<Link<'_>> {
+        let side = |color| {
+            pieces
+                .iter()
+                .filter(|p| p.1 == color && p.2 != Piece::King)
+                .map(|p| p.2)
+                .collect()
+        };
+        let material = Material::new(side(Color::White), side(Color::Black));
+
+        let mut link = Link {
+            table: None,
+            flip: false,
+            slots: [0; MAX_PIECES],
+            captured: None,
+            promotion: None,
+        };
+        if material.count() == 2 {
+            return Some(link);
+        }
+
+        let (table, flip) = match self.tables.get(&material) {
+            Some(table) => (table, false),
+            None => (self.tables.get(&material.flipped())?, true),
+        };
+        link.table = Some(table);
+        link.flip = flip;
+
+        let mut used = [false; MAX_PIECES];
+        for (slot, &(color, piece)) in table.layout.iter().enumerate() {
+            let color = if flip { !color } else { color };
+            let k = (0..pieces.len())
+                .find(|&k| !used[k] && pieces[k].1 == color && pieces[k].2 == piece)?;
+            used[k] = true;
+            link.slots[slot] = pieces[k].0;
+        }
+
+        Some(link)
+    }
+
+    /// Result of the position with perfect play, None if there is no table for it.
+    /// Positions with castling rights, an en passant capture available or variant rules
+    /// aren't covered
+    pub fn outcome(&self, game: &Game) -> Option<Outcome> {
+        let state = game.state();
+        if state.castling_rights.bits() != 0 || !game.is_standard() {
+            return None;
+        }
+        if let Some(ep) = state.en_passant {
+            let takers =
+                attacks::pawn_attacks(ep, !game.turn()) & game.piece_bb(game.turn(), Piece::Pawn);
+            if !takers.is_empty() {
+                return None;
+            }
+        }
+
+        let material = Material::from_game(game);
+        if material.count() > self.max_pieces().max(2) {
+            return None;
+        }
+
+        let mut pieces = Vec::with_capacity(MAX_PIECES);
+        let mut squares = Vec::with_capacity(MAX_PIECES);
+        for color in Color::ALL {
+            for piece in Piece::ALL {
+                for sqr in game.piece_bb(color, piece) {
+                    pieces.push((squares.len(), color, piece));
+                    squares.push(sqr);
+                }
+            }
+        }
+
+        let link = self.link(&pieces)?;
+        Some(Outcome::from_value(link.read(game.turn(), |i| squares[i])))
+    }
+
+    /// Move that keeps the best result: the quickest mate when winning, the longest
+    /// resistance when losing
+    pub fn best_move(&self, game: &mut Game) -> Option<(Move, Outcome)> {
+        let outcome = self.outcome(game)?;
+        let mut best: Option<(Move, i64)> = None;
+
+        for mv in game.generate_moves() {
+            game.make_move(mv);
+            let after = self.outcome(game);
+            game.unmake_move(mv);
+
+            // Ranked from our side, quicker wins and slower losses first
+            let rank = match after? {
+                Outcome::Loss(plies) => 1000 - plies as i64,
+                Outcome::Draw => 0,
+                Outcome::Win(plies) => -1000 + plies as i64,
+            };
+            if best.is_none_or(|(_, best)| rank > best) {
+                best = Some((mv, rank));
+            }
+        }
+
+        best.map(|(mv, _)| (mv, outcome))
+    }
+
+    /// Load every table file in a directory
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self, TablebaseError> {
+        let mut tablebase = Self::new();
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+
+            let table = read_table(&fs::read(&path)?)
+                .ok_or_else(|| TablebaseError::InvalidFile(path.display().to_string()))?;
+            tablebase.insert(table);
+        }
+
+        Ok(tablebase)
+    }
+
+    /// Write every table to a directory, one file each
+    pub fn save(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        for (material, table) in &self.tables {
+            let path = dir.join(format!("{material}.{EXTENSION}"));
+            fs::write(path, write_table(table))?;
+        }
+        Ok(())
+    }
+}
+
+// See the module docs for the layout
+fn write_table(table: &Table) -> Vec<u8> {
+    let name = table.material.to_string();
+    let mut bytes = Vec::new();
+    bytes.extend(MAGIC);
+    bytes.push(VERSION);
+    bytes.push(name.len() as u8);
+    bytes.extend(name.as_bytes());
+
+    let mut runs = Vec::new();
+    for run in table.data.chunk_by(|a, b| a == b) {
+        runs.push(if run[0] == DRAW { 0 } else { run[0] });
+        write_varint(&mut runs, run.len());
+    }
+
+    write_varint(&mut bytes, runs.len());
+    let lengths = code_lengths(&runs);
+    bytes.extend(lengths);
+
+    let codes = canonical_codes(&lengths);
+    let mut writer = BitWriter::default();
+    for &byte in &runs {
+        let (code, len) = codes[byte as usize];
+        writer.write(code, len);
+    }
+    bytes.extend(writer.finish());
+    bytes
+}
+
+fn read_table(bytes: &[u8]) -> Option<Table> {
+    let rest = bytes.strip_prefix(MAGIC)?;
+    let (&version, rest) = rest.split_first()?;
+    let (&len, rest) = rest.split_first()?;
+    if version != VERSION || rest.len() < len as usize {
+        return None;
+    }
+
+    let (name, mut rest) = rest.split_at(len as usize);
+    let material = Material::parse(std::str::from_utf8(name).ok()?).ok()?;
+    let mut table = Table::empty(material);
+
+    let run_bytes = read_varint(&mut rest)?;
+    // Each run takes at least two bytes and covers at least one position
+    if run_bytes > 2 * table.size() || rest.len() < 256 {
+        return None;
+    }
+    let (lengths, bits) = rest.split_at(256);
+    let decoder = Decoder::new(lengths.try_into().unwrap())?;
+    let mut reader = BitReader { bytes: bits, at: 0 };
+    let runs = (0..run_bytes)
+        .map(|_| decoder.decode(&mut reader))
+        .collect::<Option<Vec<u8>>>()?;
+
+    table.data.clear();
+    let mut rest = runs.as_slice();
+    while let Some((&value, after)) = rest.split_first() {
+        rest = after;
+        let len = read_varint(&mut rest)?;
+        if table.data.len() + len > table.size() {
+            return None;
+        }
+        let value = if value == 0 { DRAW } else { value };
+        table.data.extend(std::iter::repeat_n(value, len));
+    }
+
+    (table.data.len() == table.size()).then_some(table)
+}
+
+// LEB128, seven bits a byte lowest first with the top bit set on all but the last
+fn write_varint(bytes: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Option<usize> {
+    let mut value = 0;
+    for shift in (0..usize::BITS).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte < 0x80 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+// Huffman code length of each byte value, 0 for values that never appear. Built by
+// repeatedly joining the two rarest nodes, a leaf's length is how deep it ends up
+fn code_lengths(bytes: &[u8]) -> [u8; 256] {
+    let mut counts = [0u64; 256];
+    for &byte in bytes {
+        counts[byte as usize] += 1;
+    }
+
+    let mut heap: BinaryHeap<Reverse<(u64, usize)>> = (0..256)
+        .filter(|&byte| counts[byte] > 0)
+        .map(|byte| Reverse((counts[byte], byte)))
+        .collect();
+    let mut lengths = [0; 256];
+    if heap.len() == 1 {
+        let Reverse((_, byte)) = heap.pop().unwrap();
+        lengths[byte] = 1;
+        return lengths;
+    }
+
+    // Leaves are 0..256 and joined nodes come after
+    let mut parent = vec![usize::MAX; 2 * 256];
+    let mut next = 256;
+    while heap.len() > 1 {
+        let Reverse((a, left)) = heap.pop().unwrap();
+        let Reverse((b, right)) = heap.pop().unwrap();
+        parent[left] = next;
+        parent[right] = next;
+        heap.push(Reverse((a + b, next)));
+        next += 1;
+    }
+
+    for byte in (0..256).filter(|&byte| counts[byte] > 0) {
+        let mut node = byte;
+        while parent[node] != usize::MAX {
+            node = parent[node];
+            lengths[byte] += 1;
+        }
+    }
+    lengths
+}
+
+// Codes handed out in order of length then value, so the lengths alone describe them
+fn canonical_codes(lengths: &[u8; 256]) -> [(u64, u8); 256] {
+    let mut order: Vec<usize> = (0..256).filter(|&byte| lengths[byte] > 0).collect();
+    order.sort_by_key(|&byte| (lengths[byte], byte));
+
+    let mut codes = [(0, 0); 256];
+    let mut code = 0u64;
+    let mut prev = 0;
+    for byte in order {
+        code <<= lengths[byte] - prev;
+        prev = lengths[byte];
+        codes[byte] = (code, prev);
+        code += 1;
+    }
+    codes
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u64,
+    count: u8,
+}
+
+impl BitWriter {
+    // Most significant bit first
+    fn write(&mut self, code: u64, len: u8) {
+        for i in (0..len).rev() {
+            self.bits = self.bits << 1 | (code >> i) & 1;
+            self.count += 1;
+            if self.count == 8 {
+                self.bytes.push(self.bits as u8);
+                self.bits = 0;
+                self.count = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push((self.bits << (8 - self.count)) as u8);
+        }
+        self.bytes
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    // Bits read so far
+    at: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self) -> Option<u64> {
+        let byte = self.bytes.get(self.at / 8)?;
+        let bit = byte >> (7 - self.at % 8) & 1;
+        self.at += 1;
+        Some(bit as u64)
+    }
+}
+
+// Reads canonical codes a bit at a time, at each length the codes are a range starting
+// at first[len] covering the next count[len] values in length order
+struct Decoder {
+    first: Vec<u64>,
+    count: Vec<u64>,
+    // Where each length's values start in order
+    offset: Vec<usize>,
+    order: Vec<u8>,
+}
+
+impl Decoder {
+    fn new(lengths: &[u8; 256]) -> Option<Self> {
+        let max = *lengths.iter().max()? as usize;
+        if max == 0 || max > 63 {
+            return None;
+        }
+
+        let mut order: Vec<u8> = (0..=255)
+            .filter(|&byte| lengths[byte as usize] > 0)
+            .collect();
+        order.sort_by_key(|&byte| (lengths[byte as usize], byte));
+
+        let mut count = vec![0; max + 1];
+        for &byte in &order {
+            count[lengths[byte as usize] as usize] += 1;
+        }
+        let mut first = vec![0; max + 1];
+        let mut offset = vec![0; max + 1];
+        let mut code = 0;
+        let mut index = 0;
+        for len in 1..=max {
+            first[len] = code;
+            offset[len] = index;
+            code = (code + count[len]) << 1;
+            index += count[len] as usize;
+        }
+
+        Some(Self {
+            first,
+            count,
+            offset,
+            order,
+        })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Option<u8> {
+        let mut code = 0;
+        for len in 1..self.first.len() {
+            code = code << 1 | reader.read()?;
+            if code.wrapping_sub(self.first[len]) < self.count[len] {
+                let index = self.offset[len] + (code - self.first[len]) as usize;
+                return Some(self.order[index]);
+            }
+        }
+        None
+    }
+}
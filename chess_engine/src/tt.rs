@@ -0,0 +1,223 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::{
+    game::{LegalMove, Move},
+    piece::Piece,
+    square::Square,
+};
+
+pub const DEFAULT_SIZE_MB: usize = 16;
+
+// What the stored score says about the real score of the position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    // Caused a beta cutoff so the real score is at least this
+    Lower,
+    // Nothing beat alpha so the real score is at most this
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Entry {
+    pub key: u64,
+    pub depth: u8,
+    pub score: i32,
+    pub bound: Bound,
+    pub best_move: Option<Move>,
+    // Search the entry was written in, older entries are always replaced
+    age: u8,
+}
+
+// One entry packed into a pair of words. The key is stored xored with the data so a slot
+// half written by one thread while another reads it fails the key check instead of
+// handing back a mix of two entries
+#[derive(Default)]
+struct Slot {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+/// Fixed size hash table of search results, indexed by Zobrist key. Shared between search
+/// threads without locking
+pub struct TranspositionTable {
+    entries: Vec<Slot>,
+    age: AtomicU8,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_SIZE_MB)
+    }
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        let mut tt = Self {
+            entries: Vec::new(),
+            age: AtomicU8::new(0),
+        };
+        tt.resize(size_mb);
+        tt
+    }
+
+    /// Change the size of the table, this throws away everything in it
+    pub fn resize(&mut self, size_mb: usize) {
+        let count = (size_mb * 1024 * 1024 / std::mem::size_of::<Slot>()).max(1);
+        self.entries = (0..count).map(|_| Slot::default()).collect();
+    }
+
+    /// Forget everything, should be called between games
+    pub fn clear(&self) {
+        for slot in &self.entries {
+            slot.check.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+        self.age.store(0, Ordering::Relaxed);
+    }
+
+    /// Mark the start of a new search so entries from older ones get replaced first
+    pub fn new_search(&self) {
+        self.age.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key % self.entries.len() as u64) as usize
+    }
+
+    fn load(&self, index: usize) -> Option<Entry> {
+        let slot = &self.entries[index];
+        let data = slot.data.load(Ordering::Relaxed);
+        let key = slot.check.load(Ordering::Relaxed) ^ data;
+        unpack(key, data)
+    }
+
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        self.load(self.index(key)).filter(|entry| entry.key == key)
+    }
+
+    /// Store a result, keeping whichever of the old and new entry was searched deeper
+    pub fn store(&self, key: u64, depth: u8, score: i32, bound: Bound, best_move: Option<Move>) {
+        let index = self.index(key);
+        let age = self.age.load(Ordering::Relaxed);
+        let old = self.load(index);
+
+        if let Some(old) = old {
+            if old.age == age && old.depth > depth {
+                return;
+            }
+        }
+
+        // Keep the old move if this search didn't find one for the same position
+        let best_move = match old {
+            Some(old) if old.key == key && best_move.is_none() => old.best_move,
+            _ => best_move,
+        };
+
+        let data = pack(&Entry {
+            key,
+            depth,
+            score,
+            bound,
+            best_move,
+            age,
+        });
+        let slot = &self.entries[index];
+        slot.check.store(key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    /// Permille of the table in use by the current search, for reporting
+    pub fn hashfull(&self) -> usize {
+        let age = self.age.load(Ordering::Relaxed);
+        let sample = self.entries.len().min(1000);
+        (0..sample)
+            .filter(|&index| self.load(index).is_some_and(|e| e.age == age))
+            .count()
+            * 1000
+            / sample
+    }
+}
+
+// Data word layout, from the lowest bit:
+//   score 16 | depth 8 | age 8 | bound 2 | move 19
+// An all zero word is an empty slot, so the bound is stored off by one
+fn pack(entry: &Entry) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+
+    (entry.score as i16 as u16 as u64)
+        | (entry.depth as u64) << 16
+        | (entry.age as u64) << 24
+        | bound << 32
+        | pack_move(entry.best_move) << 34
+}
+
+fn unpack(key: u64, data: u64) -> Option<Entry> {
+    let bound = match (data >> 32) & 0b11 {
+        1 => Bound::Exact,
+        2 => Bound::Lower,
+        3 => Bound::Upper,
+        _ => return None,
+    };
+
+    Some(Entry {
+        key,
+        score: data as u16 as i16 as i32,
+        depth: (data >> 16) as u8,
+        age: (data >> 24) as u8,
+        bound,
+        best_move: unpack_move(data >> 34),
+    })
+}
+
+const KINDS: [LegalMove; 5] = [
+    LegalMove::Move,
+    LegalMove::Attack,
+    LegalMove::Promotion,
+    LegalMove::Castle,
+    LegalMove::EnPassant,
+];
+// Comes after the others, the piece goes where a promotion would
+const DROP: u64 = KINDS.len() as u64;
+
+// from 6 | to 6 | kind 3 | promotion or dropped piece 3 | present 1
+fn pack_move(mv: Option<Move>) -> u64 {
+    let Some(mv) = mv else {
+        return 0;
+    };
+    let kind = match mv.kind {
+        LegalMove::Drop(_) => DROP,
+        kind => KINDS.iter().position(|&k| k == kind).unwrap() as u64,
+    };
+    let promotion = mv
+        .promotion
+        .or(mv.dropped())
+        .map_or(0, |piece| piece as u64 + 1);
+
+    mv.from.index() as u64 | (mv.to.index() as u64) << 6 | kind << 12 | promotion << 15 | 1 << 18
+}
+
+fn unpack_move(bits: u64) -> Option<Move> {
+    if bits & 1 << 18 == 0 {
+        return None;
+    }
+
+    let from = Square::from_index(bits as usize & 63)?;
+    let to = Square::from_index((bits >> 6) as usize & 63)?;
+    let piece = match (bits >> 15) & 0b111 {
+        0 => None,
+        piece => Some(Piece::ALL[piece as usize - 1]),
+    };
+    let kind = (bits >> 12) & 0b111;
+    if kind == DROP {
+        return Some(Move::drop(piece?, to));
+    }
+
+    let mut mv = Move::new(from, to, *KINDS.get(kind as usize)?);
+    mv.promotion = piece;
+    Some(mv)
+}
@@ -0,0 +1,174 @@
+use std::fmt::Display;
+
+use crate::{
+    board::BitBoard,
+    game::Game,
+    piece::{Color, Piece},
+    square::Square,
+    state::CastlingRights,
+};
+
+// Most of each piece a side starts with, anything over that has to be a promoted pawn
+const START_COUNTS: [u32; Piece::COUNT] = [8, 2, 2, 2, 1, 1];
+const MAX_PIECES: u32 = 16;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PositionError {
+    // The side and how many kings it has
+    KingCount(Color, u32),
+    PawnOnBackRank(Square),
+    // The side that just moved left its king attacked
+    OpponentInCheck,
+    // A castling right without the king and rook where they start from, the bool is kingside
+    InvalidCastling(Color, bool),
+    InvalidEnPassant(Square),
+    TooManyPieces(Color),
+    TooManyPawns(Color),
+    // More queens, rooks, bishops or knights than the missing pawns could have promoted to
+    TooManyPromotions(Color),
+}
+
+impl Display for PositionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PositionError::KingCount(side, count) => {
+                write!(f, "Expected one {side} king, found {count}")
+            }
+            PositionError::PawnOnBackRank(sqr) => write!(f, "Pawn on {sqr} is on a back rank"),
+            PositionError::OpponentInCheck => write!(f, "The side not to move is in check"),
+            PositionError::InvalidCastling(side, kingside) => write!(
+                f,
+                "The {side} king or rook needed to castle {} has moved",
+                if *kingside { "kingside" } else { "queenside" }
+            ),
+            PositionError::InvalidEnPassant(sqr) => {
+                write!(f, "No pawn could have just passed {sqr} for en passant")
+            }
+            PositionError::TooManyPieces(side) => {
+                write!(f, "More than {MAX_PIECES} {side} pieces")
+            }
+            PositionError::TooManyPawns(side) => write!(f, "More than 8 {side} pawns"),
+            PositionError::TooManyPromotions(side) => {
+                write!(f, "More promoted {side} pieces than missing {side} pawns")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+impl Game {
+    /// Check the position could come about in a real game, as far as can be told without
+    /// the moves. FEN and setting pieces up by hand will take anything, search and move
+    /// generation expect a position this passes
+    pub fn validate(&self) -> Result<(), PositionError> {
+        for side in Color::ALL {
+            let kings = self.piece_bb(side, Piece::King).count();
+            if kings != 1 {
+                return Err(PositionError::KingCount(side, kings));
+            }
+        }
+
+        let back_ranks = BitBoard(0xFF | 0xFF << 56);
+        for side in Color::ALL {
+            if let Some(sqr) = (self.piece_bb(side, Piece::Pawn) & back_ranks).lsb() {
+                return Err(PositionError::PawnOnBackRank(sqr));
+            }
+        }
+
+        if self.in_check(!self.turn()) {
+            return Err(PositionError::OpponentInCheck);
+        }
+
+        for side in Color::ALL {
+            for kingside in [true, false] {
+                if !self.castling_valid(side, kingside) {
+                    return Err(PositionError::InvalidCastling(side, kingside));
+                }
+            }
+        }
+
+        if let Some(ep) = self.state().en_passant {
+            if !self.en_passant_valid(ep) {
+                return Err(PositionError::InvalidEnPassant(ep));
+            }
+        }
+
+        // Captured pieces change sides in Crazyhouse so the counts only hold without pockets
+        if !self.has_pockets() {
+            for side in Color::ALL {
+                self.validate_material(side)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // A right is fine if it isn't there, or the king and rook are where it needs them.
+    // Outside Chess960 that is also the squares they start on in normal chess
+    fn castling_valid(&self, side: Color, kingside: bool) -> bool {
+        let right = if kingside {
+            CastlingRights::kingside(side)
+        } else {
+            CastlingRights::queenside(side)
+        };
+        if !self.state().castling_rights.has(right) {
+            return true;
+        }
+
+        let rank = side.back_rank();
+        let Some(king) = self.piece_bb(side, Piece::King).lsb() else {
+            return false;
+        };
+        let file = self.castling_file(side, kingside);
+        let rook = Square::new(file, rank).unwrap();
+
+        let corner = if kingside { 7 } else { 0 };
+        let usual = king.file() == 4 && file == corner;
+        king.rank() == rank
+            && (self.is_chess960() || usual)
+            && (file > king.file()) == kingside
+            && self.piece_at(rook) == Some((side, Piece::Rook))
+    }
+
+    // The square behind a pawn that has just moved two squares, so both it and the square
+    // the pawn came from are empty
+    fn en_passant_valid(&self, ep: Square) -> bool {
+        let pusher = !self.turn();
+        let forward = pusher.forward();
+        if ep.rank() as i8 != pusher.back_rank() as i8 + 2 * forward {
+            return false;
+        }
+
+        let (Some(pawn), Some(start)) = (ep.offset(0, forward), ep.offset(0, -forward)) else {
+            return false;
+        };
+        self.piece_at(pawn) == Some((pusher, Piece::Pawn))
+            && self.piece_at(ep).is_none()
+            && self.piece_at(start).is_none()
+    }
+
+    fn validate_material(&self, side: Color) -> Result<(), PositionError> {
+        if self.color_bb(side).count() > MAX_PIECES {
+            return Err(PositionError::TooManyPieces(side));
+        }
+
+        let pawns = self.piece_bb(side, Piece::Pawn).count();
+        if pawns > START_COUNTS[Piece::Pawn as usize] {
+            return Err(PositionError::TooManyPawns(side));
+        }
+
+        let promoted: u32 = Piece::PROMOTIONS
+            .into_iter()
+            .map(|piece| {
+                let count = self.piece_bb(side, piece).count();
+                count.saturating_sub(START_COUNTS[piece as usize])
+            })
+            .sum();
+        if promoted > START_COUNTS[Piece::Pawn as usize] - pawns {
+            return Err(PositionError::TooManyPromotions(side));
+        }
+
+        Ok(())
+    }
+}
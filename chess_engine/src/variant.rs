@@ -0,0 +1,247 @@
+use std::fmt::Debug;
+
+use crate::{
+    attacks,
+    board::BitBoard,
+    game::{Game, Move},
+    piece::{Color, Piece},
+    zobrist::KEYS,
+};
+
+/// Rules layered on top of normal chess. A game holds one for its whole length and asks it
+/// about legality, what else happens when a move is played and whether someone has won
+pub trait Variant: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether the usual move generator, which works out pins and check evasions itself,
+    /// gets legality right. If not every pseudo legal move is played and is_legal asked
+    fn standard_legality(&self) -> bool {
+        true
+    }
+
+    /// Whether the side that just moved has left the board in a legal state, only asked
+    /// when standard_legality is false
+    fn is_legal(&self, game: &Game, side: Color) -> bool {
+        !game.in_check(side)
+    }
+
+    /// Whether captured pieces can be dropped back on the board, which puts the pockets in
+    /// the FEN. Pieces in a pocket can be dropped whatever this says
+    fn drops(&self) -> bool {
+        false
+    }
+
+    /// Called by make_move with the move on the board but before the turn passes to the
+    /// other side. Pieces taken off with Game::remove_piece are put back by unmake_move
+    fn after_move(&self, _game: &mut Game, _mv: Move) {}
+
+    /// Pieces giving check to the side to move, given the ones that attack its king
+    fn checkers(&self, _game: &Game, attackers: BitBoard) -> BitBoard {
+        attackers
+    }
+
+    /// Side that has won by the variant's own rules. Checkmate and the draw rules are
+    /// still handled as normal
+    fn winner(&self, _game: &Game) -> Option<Color> {
+        None
+    }
+}
+
+/// Normal chess
+#[derive(Debug)]
+pub struct Standard;
+
+impl Variant for Standard {
+    fn name(&self) -> &'static str {
+        "Standard"
+    }
+}
+
+/// Getting your king to one of the four centre squares wins
+#[derive(Debug)]
+pub struct KingOfTheHill;
+
+// d4, e4, d5 and e5
+const HILL: BitBoard = BitBoard(0x0000_0018_1800_0000);
+
+impl Variant for KingOfTheHill {
+    fn name(&self) -> &'static str {
+        "King of the Hill"
+    }
+
+    fn winner(&self, game: &Game) -> Option<Color> {
+        Color::ALL
+            .into_iter()
+            .find(|&side| !(game.piece_bb(side, Piece::King) & HILL).is_empty())
+    }
+}
+
+/// Giving check three times wins, the count is kept in State::checks
+#[derive(Debug)]
+pub struct ThreeCheck;
+
+pub const CHECKS_TO_WIN: u8 = 3;
+
+impl Variant for ThreeCheck {
+    fn name(&self) -> &'static str {
+        "Three-check"
+    }
+
+    fn after_move(&self, game: &mut Game, _mv: Move) {
+        let side = game.turn();
+        let Some(king) = game.piece_bb(!side, Piece::King).lsb() else {
+            return;
+        };
+
+        if !game.attackers_to(king, side).is_empty() {
+            let state = game.state_mut();
+            let checks = &mut state.checks[side as usize];
+            state.hash ^= KEYS.checks(side, *checks);
+            *checks += 1;
+            state.hash ^= KEYS.checks(side, *checks);
+        }
+    }
+
+    fn winner(&self, game: &Game) -> Option<Color> {
+        Color::ALL
+            .into_iter()
+            .find(|&side| game.state().checks[side as usize] >= CHECKS_TO_WIN)
+    }
+}
+
+/// Captures explode, taking out the capturing piece and every piece but pawns next to the
+/// square. Blowing up the enemy king wins and kings can't capture
+#[derive(Debug)]
+pub struct Atomic;
+
+impl Variant for Atomic {
+    fn name(&self) -> &'static str {
+        "Atomic"
+    }
+
+    // Kings next to each other can't check one another and a capture near the enemy king
+    // can end the game while in check, neither of which the usual generator knows about
+    fn standard_legality(&self) -> bool {
+        false
+    }
+
+    fn is_legal(&self, game: &Game, side: Color) -> bool {
+        if game.piece_bb(side, Piece::King).is_empty() {
+            return false;
+        }
+        if game.piece_bb(!side, Piece::King).is_empty() {
+            return true;
+        }
+        kings_touching(game) || !game.in_check(side)
+    }
+
+    fn after_move(&self, game: &mut Game, mv: Move) {
+        if game.state().captured.is_none() {
+            return;
+        }
+
+        // The capturing piece goes along with the one it took
+        game.remove_piece(mv.to);
+        for sqr in attacks::king_attacks(mv.to) {
+            if let Some((_, piece)) = game.piece_at(sqr) {
+                if piece != Piece::Pawn {
+                    game.remove_piece(sqr);
+                }
+            }
+        }
+    }
+
+    fn checkers(&self, game: &Game, attackers: BitBoard) -> BitBoard {
+        if kings_touching(game) {
+            BitBoard(0)
+        } else {
+            attackers
+        }
+    }
+
+    fn winner(&self, game: &Game) -> Option<Color> {
+        Color::ALL
+            .into_iter()
+            .find(|&side| game.piece_bb(!side, Piece::King).is_empty())
+    }
+}
+
+// Taking the other king would blow up your own so neither can be attacked
+fn kings_touching(game: &Game) -> bool {
+    match (
+        game.piece_bb(Color::White, Piece::King).lsb(),
+        game.piece_bb(Color::Black, Piece::King).lsb(),
+    ) {
+        (Some(white), Some(black)) => {
+            !(attacks::king_attacks(white) & BitBoard::from_square(black)).is_empty()
+        }
+        _ => false,
+    }
+}
+
+/// Captured pieces change sides and go in the capturer's pocket, from where they can be
+/// dropped on any empty square instead of moving
+#[derive(Debug)]
+pub struct Crazyhouse;
+
+impl Variant for Crazyhouse {
+    fn name(&self) -> &'static str {
+        "Crazyhouse"
+    }
+
+    fn drops(&self) -> bool {
+        true
+    }
+
+    fn after_move(&self, game: &mut Game, _mv: Move) {
+        if let Some(piece) = game.pocketed() {
+            game.add_to_pocket(game.turn(), piece);
+        }
+    }
+}
+
+/// Crazyhouse played by two teams on two boards. Drops work the same but captures go to
+/// the partner on the other board, which is up to whoever runs both games with
+/// Game::pocketed and Game::add_to_pocket
+#[derive(Debug)]
+pub struct Bughouse;
+
+impl Variant for Bughouse {
+    fn name(&self) -> &'static str {
+        "Bughouse"
+    }
+
+    fn drops(&self) -> bool {
+        true
+    }
+}
+
+pub static STANDARD: Standard = Standard;
+pub static KING_OF_THE_HILL: KingOfTheHill = KingOfTheHill;
+pub static THREE_CHECK: ThreeCheck = ThreeCheck;
+pub static ATOMIC: Atomic = Atomic;
+pub static CRAZYHOUSE: Crazyhouse = Crazyhouse;
+pub static BUGHOUSE: Bughouse = Bughouse;
+
+/// Every variant the engine can play
+pub static VARIANTS: [&dyn Variant; 6] = [
+    &STANDARD,
+    &KING_OF_THE_HILL,
+    &THREE_CHECK,
+    &ATOMIC,
+    &CRAZYHOUSE,
+    &BUGHOUSE,
+];
+
+/// Variant from its name ignoring case, spaces and dashes, e.g. kingofthehill or three-check
+pub fn by_name(name: &str) -> Option<&'static dyn Variant> {
+    let simplify = |name: &str| -> String {
+        name.chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_lowercase())
+            .collect()
+    };
+    VARIANTS
+        .into_iter()
+        .find(|variant| simplify(variant.name()) == simplify(name))
+}
@@ -0,0 +1,141 @@
+use crate::{
+    piece::{Color, Piece},
+    square::Square,
+};
+
+// Random numbers xored together to give each position a (nearly) unique key
+pub struct Zobrist {
+    pieces: [[[u64; 64]; 6]; 2],
+    side: u64,
+    castling: [u64; 16],
+    en_passant: [u64; 8],
+    // Checks given so far in Three-check, no checks is left as zero
+    checks: [[u64; 4]; 2],
+    // How many of each piece is in a pocket, an empty pocket is left as zero
+    pockets: [[[u64; MAX_POCKET + 1]; 5]; 2],
+}
+
+// Counts beyond this share a key, only reachable when Bughouse partners pass a lot over
+const MAX_POCKET: usize = 16;
+
+// Fixed seed so keys are the same every run
+const SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+pub const KEYS: Zobrist = Zobrist::new();
+
+// xorshift64* pseudo random number generator, usable in a const context
+pub(crate) const fn next(state: u64) -> (u64, u64) {
+    let mut x = state;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    (x, x.wrapping_mul(0x2545_F491_4F6C_DD1D))
+}
+
+impl Zobrist {
+    const fn new() -> Self {
+        let mut state = SEED;
+        let mut keys = Zobrist {
+            pieces: [[[0; 64]; 6]; 2],
+            side: 0,
+            castling: [0; 16],
+            en_passant: [0; 8],
+            checks: [[0; 4]; 2],
+            pockets: [[[0; MAX_POCKET + 1]; 5]; 2],
+        };
+
+        let mut side = 0;
+        while side < 2 {
+            let mut piece = 0;
+            while piece < 6 {
+                let mut sqr = 0;
+                while sqr < 64 {
+                    let (s, key) = next(state);
+                    state = s;
+                    keys.pieces[side][piece][sqr] = key;
+                    sqr += 1;
+                }
+                piece += 1;
+            }
+            side += 1;
+        }
+
+        let (s, key) = next(state);
+        state = s;
+        keys.side = key;
+
+        let mut i = 0;
+        while i < 16 {
+            let (s, key) = next(state);
+            state = s;
+            keys.castling[i] = key;
+            i += 1;
+        }
+
+        let mut i = 0;
+        while i < 8 {
+            let (s, key) = next(state);
+            state = s;
+            keys.en_passant[i] = key;
+            i += 1;
+        }
+
+        let mut side = 0;
+        while side < 2 {
+            let mut i = 1;
+            while i < 4 {
+                let (s, key) = next(state);
+                state = s;
+                keys.checks[side][i] = key;
+                i += 1;
+            }
+            side += 1;
+        }
+
+        let mut side = 0;
+        while side < 2 {
+            let mut piece = 0;
+            while piece < 5 {
+                let mut i = 1;
+                while i <= MAX_POCKET {
+                    let (s, key) = next(state);
+                    state = s;
+                    keys.pockets[side][piece][i] = key;
+                    i += 1;
+                }
+                piece += 1;
+            }
+            side += 1;
+        }
+
+        keys
+    }
+
+    pub fn piece(&self, side: Color, piece: Piece, sqr: Square) -> u64 {
+        self.pieces[side as usize][piece as usize][sqr.index()]
+    }
+
+    // Xored in when black is to move
+    pub fn side(&self) -> u64 {
+        self.side
+    }
+
+    pub fn castling(&self, rights: u8) -> u64 {
+        self.castling[rights as usize & 0xF]
+    }
+
+    pub fn checks(&self, side: Color, checks: u8) -> u64 {
+        self.checks[side as usize][checks.min(3) as usize]
+    }
+
+    pub fn pocket(&self, side: Color, piece: Piece, count: u8) -> u64 {
+        self.pockets[side as usize][piece as usize][(count as usize).min(MAX_POCKET)]
+    }
+
+    pub fn en_passant(&self, sqr: Option<Square>) -> u64 {
+        match sqr {
+            Some(sqr) => self.en_passant[sqr.file() as usize],
+            None => 0,
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::engine::{self, EngineConfig};
+use crate::game::GameEvent;
+use crate::player::Player;
+use crate::setup;
+use axum::extract::ws::Message;
+use chess_engine::game::Game;
+use chess_engine::piece::{Color, Piece};
+use chess_engine::search::Limits;
+use log::info;
+use tokio::task::JoinHandle;
+
+// How a game against the engine finished, from the player's side
+enum Outcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+/// A single player against the engine, the player has white. The engine can start a
+/// piece down as odds
+pub fn start(mut player: Player, config: EngineConfig, odds: Option<Piece>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut game = player.variant().start_position();
+        if let Some(piece) = odds {
+            setup::give_odds(&mut game, Color::Black, piece);
+        }
+        let mut search = config.opponent();
+
+        let start = GameEvent::new("START", Some(game.to_fen()));
+        if player.sock().send(start.to_message()).await.is_err() {
+            return;
+        }
+
+        while let Some(Ok(msg)) = player.sock().recv().await {
+            let Message::Text(txt) = msg else {
+                continue;
+            };
+            let Ok(evt) = serde_json::from_str::<GameEvent>(&txt) else {
+                continue;
+            };
+
+            match evt.msg_type.as_str() {
+                "MOVE" => {}
+                "RESIGN" => {
+                    send_outcome(&mut player, Outcome::Loss).await;
+                    break;
+                }
+                _ => continue,
+            }
+
+            let Some(mv) = evt.data.and_then(|data| game.find_move(&data)) else {
+                let error = GameEvent::new("ERROR", Some("Illegal move".into()));
+                if player.sock().send(error.to_message()).await.is_err() {
+                    break;
+                }
+                continue;
+            };
+            game.make_move(mv);
+
+            if let Some(outcome) = outcome(&mut game) {
+                send_outcome(&mut player, outcome).await;
+                break;
+            }
+
+            let limits = Limits::movetime(config.movetime);
+            let (returned, result) = engine::think(search, game.clone(), limits).await;
+            search = returned;
+
+            // A search stopped straight away may have nothing, any legal move will do then
+            let reply = result
+                .best_move
+                .or_else(|| game.generate_moves().first().copied());
+            let Some(reply) = reply else {
+                // No moves left in a way outcome didn't see, nobody can go on
+                send_outcome(&mut player, Outcome::Draw).await;
+                break;
+            };
+            info!(
+                "Engine plays {reply} scoring {} at depth {}",
+                result.score, result.depth
+            );
+            game.make_move(reply);
+
+            let event = GameEvent::new("MOVE", Some(game.coordinates(reply)));
+            if player.sock().send(event.to_message()).await.is_err() {
+                break;
+            }
+
+            if let Some(outcome) = outcome(&mut game) {
+                send_outcome(&mut player, outcome).await;
+                break;
+            }
+        }
+    })
+}
+
+// How the game ended for the player, None while it is still going
+fn outcome(game: &mut Game) -> Option<Outcome> {
+    // Won by the variant's rules, e.g. a third check
+    if let Some(winner) = game.winner() {
+        return Some(if winner == Color::White {
+            Outcome::Win
+        } else {
+            Outcome::Loss
+        });
+    }
+
+    if game.generate_moves().is_empty() {
+        // Whoever moved last either mated or stalemated the side now to move
+        let mated = !game.checkers().is_empty();
+        let player_moved = game.turn() == Color::Black;
+
+        return Some(match (mated, player_moved) {
+            (false, _) => Outcome::Draw,
+            (true, true) => Outcome::Win,
+            (true, false) => Outcome::Loss,
+        });
+    }
+
+    if game.state().halfmove_clock >= 100 || game.is_threefold() {
+        return Some(Outcome::Draw);
+    }
+
+    None
+}
+
+async fn send_outcome(player: &mut Player, outcome: Outcome) {
+    let data = match outcome {
+        Outcome::Win => "WINNER",
+        Outcome::Loss => "LOSER",
+        Outcome::Draw => "DRAW",
+    };
+
+    let event = GameEvent::new("GAME_OVER", Some(data.into()));
+    let _ = player.sock().send(event.to_message()).await;
+}
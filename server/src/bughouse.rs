@@ -0,0 +1,238 @@
+use crate::game::GameEvent;
+use crate::player::Player;
+use crate::variant::Variant;
+use axum::extract::ws::Message;
+use chess_engine::game::Game;
+use chess_engine::piece::Color;
+use futures::future::select_all;
+use log::info;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep_until, Instant};
+
+// Time each player has for the whole game
+const CLOCK: Duration = Duration::from_secs(180);
+const BOARD_NAMES: [&str; 2] = ["A", "B"];
+
+// One of the two linked boards, each has its own pair of clocks
+struct Board {
+    game: Game,
+    // Time left for white and black, not counting the current turn
+    clocks: [Duration; Color::COUNT],
+    // When the side to move started thinking
+    turn_started: Instant,
+}
+
+impl Board {
+    fn new() -> Self {
+        Self {
+            game: Variant::Bughouse.start_position(),
+            clocks: [CLOCK; Color::COUNT],
+            turn_started: Instant::now(),
+        }
+    }
+
+    // When the side to move runs out of time
+    fn flag_time(&self) -> Instant {
+        self.turn_started + self.clocks[self.game.turn() as usize]
+    }
+
+    // Time left for both sides in milliseconds, white first
+    fn clock_data(&self, now: Instant) -> String {
+        let mut clocks = self.clocks;
+        let turn = self.game.turn() as usize;
+        clocks[turn] = clocks[turn].saturating_sub(now - self.turn_started);
+        format!("{} {}", clocks[0].as_millis(), clocks[1].as_millis())
+    }
+}
+
+// How the game finished, the winning team is 0 or 1
+enum End {
+    Won(usize),
+    Draw,
+}
+
+// Seats are in the order the players were matched, white then black on board A then B.
+// Partners play opposite colours so seats 0 and 3 are one team and 1 and 2 the other
+fn seat(index: usize) -> (usize, Color) {
+    (index / 2, Color::ALL[index % 2])
+}
+
+fn player_at(board: usize, side: Color) -> usize {
+    board * 2 + side as usize
+}
+
+fn team(index: usize) -> usize {
+    match index {
+        0 | 3 => 0,
+        _ => 1,
+    }
+}
+
+fn partner(index: usize) -> usize {
+    3 - index
+}
+
+/// Start a Bughouse game between four matched players
+pub fn start(players: [Player; 4]) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        Bughouse {
+            players,
+            boards: [Board::new(), Board::new()],
+        }
+        .run()
+        .await
+    })
+}
+
+struct Bughouse {
+    players: [Player; 4],
+    boards: [Board; 2],
+}
+
+impl Bughouse {
+    async fn run(mut self) {
+        for index in 0..self.players.len() {
+            let (board, side) = seat(index);
+            let fen = self.boards[board].game.to_fen();
+            self.send(index, GameEvent::new("START", Some(fen))).await;
+            // Which board and colour the player has, e.g. A white
+            let colour = match side {
+                Color::White => "white",
+                Color::Black => "black",
+            };
+            let data = format!("{} {colour}", BOARD_NAMES[board]);
+            self.send(index, GameEvent::new("SEAT", Some(data))).await;
+        }
+
+        let end = loop {
+            let deadline = self.boards.iter().map(Board::flag_time).min().unwrap();
+            let received = tokio::select! {
+                (msg, index, rest) = select_all(
+                    self.players.iter_mut().map(|player| Box::pin(player.sock().recv())),
+                ) => {
+                    drop(rest);
+                    Some((index, msg))
+                }
+                _ = sleep_until(deadline) => None,
+            };
+
+            let Some((index, msg)) = received else {
+                // Someone's flag fell, their team loses
+                let now = Instant::now();
+                let board = self
+                    .boards
+                    .iter()
+                    .position(|b| b.flag_time() <= now)
+                    .unwrap();
+                let loser = player_at(board, self.boards[board].game.turn());
+                break End::Won(1 - team(loser));
+            };
+
+            let txt = match msg {
+                Some(Ok(Message::Text(txt))) => txt,
+                Some(Ok(_)) => continue,
+                // Leaving is the same as resigning
+                Some(Err(_)) | None => break End::Won(1 - team(index)),
+            };
+            let Ok(evt) = serde_json::from_str::<GameEvent>(&txt) else {
+                continue;
+            };
+
+            match evt.msg_type.as_str() {
+                "MOVE" => {
+                    if let Some(end) = self.play(index, evt.data.unwrap_or_default()).await {
+                        break end;
+                    }
+                }
+                // Chat only goes to the partner, the other team can't read the plans
+                "CHAT" => {
+                    let chat = GameEvent::new("CHAT", evt.data);
+                    self.send(partner(index), chat).await;
+                }
+                "RESIGN" => break End::Won(1 - team(index)),
+                _ => {}
+            }
+        };
+
+        for index in 0..self.players.len() {
+            let data = match end {
+                End::Won(winner) if winner == team(index) => "WINNER",
+                End::Won(_) => "LOSER",
+                End::Draw => "DRAW",
+            };
+            self.send(index, GameEvent::new("GAME_OVER", Some(data.into())))
+                .await;
+        }
+        info!("Bughouse game over");
+    }
+
+    // Play a move for a player on their board, returns how the game ended if it did
+    async fn play(&mut self, index: usize, text: String) -> Option<End> {
+        let (b, side) = seat(index);
+        let now = Instant::now();
+        let board = &mut self.boards[b];
+        // The move may have got here just after the flag fell
+        if board.game.turn() == side && board.flag_time() <= now {
+            return Some(End::Won(1 - team(index)));
+        }
+
+        let mv = (board.game.turn() == side)
+            .then(|| board.game.find_move(&text))
+            .flatten();
+        let Some(mv) = mv else {
+            self.send(index, GameEvent::new("ERROR", Some("Illegal move".into())))
+                .await;
+            return None;
+        };
+
+        let clock = &mut board.clocks[side as usize];
+        *clock = clock.saturating_sub(now - board.turn_started);
+        board.turn_started = now;
+        let coordinates = board.game.coordinates(mv);
+        board.game.make_move(mv);
+        let clocks = board.clock_data(now);
+
+        // What was taken goes to the partner, who has the other colour on the other board
+        let pocketed = board.game.pocketed();
+        // Either board finishing ends the whole game. Mate only counts what is in the
+        // pocket now, not what the partner might pass over later
+        let end = if !board.game.generate_moves().is_empty() {
+            None
+        } else if board.game.checkers().is_empty() {
+            Some(End::Draw)
+        } else {
+            Some(End::Won(team(index)))
+        };
+
+        let name = BOARD_NAMES[b];
+        self.broadcast(GameEvent::new(
+            "MOVE",
+            Some(format!("{name} {coordinates}")),
+        ))
+        .await;
+        self.broadcast(GameEvent::new("CLOCK", Some(format!("{name} {clocks}"))))
+            .await;
+
+        if let Some(piece) = pocketed {
+            let other = &mut self.boards[1 - b];
+            other.game.add_to_pocket(!side, piece);
+            let data = format!("{} {}", BOARD_NAMES[1 - b], other.game.to_fen());
+            self.broadcast(GameEvent::new("POSITION", Some(data))).await;
+        }
+
+        end
+    }
+
+    async fn send(&mut self, index: usize, event: GameEvent) {
+        let _ = self.players[index].sock().send(event.to_message()).await;
+    }
+
+    // Everyone sees both boards
+    async fn broadcast(&mut self, event: GameEvent) {
+        for index in 0..self.players.len() {
+            self.send(index, GameEvent::new(&event.msg_type, event.data.clone()))
+                .await;
+        }
+    }
+}
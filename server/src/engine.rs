@@ -0,0 +1,79 @@
+use chess_engine::book::Book;
+use chess_engine::game::Game;
+use chess_engine::search::{Limits, Search, SearchResult};
+use log::{info, warn};
+use std::env;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_HASH_MB: usize = 64;
+const DEFAULT_MOVETIME_MS: u64 = 1000;
+
+/// Settings shared by every engine the server runs, read from the environment
+#[derive(Clone, Debug)]
+pub struct EngineConfig {
+    pub threads: usize,
+    pub hash_mb: usize,
+    // How long the engine opponent thinks about each move
+    pub movetime: Duration,
+    // Opening book the engine opponent plays from, loaded once and shared by every game
+    pub book: Option<Arc<Book>>,
+}
+
+impl EngineConfig {
+    pub fn from_env() -> Self {
+        Self {
+            threads: var("ENGINE_THREADS")
+                .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get())),
+            hash_mb: var("ENGINE_HASH_MB").unwrap_or(DEFAULT_HASH_MB),
+            movetime: Duration::from_millis(
+                var("ENGINE_MOVETIME_MS").unwrap_or(DEFAULT_MOVETIME_MS),
+            ),
+            book: env::var("ENGINE_BOOK").ok().and_then(load_book),
+        }
+    }
+
+    pub fn search(&self) -> Search {
+        let mut search = Search::new();
+        search.set_threads(self.threads);
+        search.set_hash_size(self.hash_mb);
+        search
+    }
+
+    // Games against the engine use the book, analysis always searches
+    pub fn opponent(&self) -> Search {
+        let mut search = self.search();
+        search.set_book(self.book.clone());
+        search
+    }
+}
+
+fn load_book(path: String) -> Option<Arc<Book>> {
+    match Book::open(&path) {
+        Ok(book) => {
+            info!("Loaded opening book {path} with {} entries", book.len());
+            Some(Arc::new(book))
+        }
+        Err(err) => {
+            warn!("Not using opening book {path}: {err}");
+            None
+        }
+    }
+}
+
+fn var<T: FromStr>(name: &str) -> Option<T> {
+    env::var(name).ok().and_then(|value| value.parse().ok())
+}
+
+// Searches hog their threads so they run on the blocking pool, not the async workers.
+// The search is handed back so its hash table carries over to the next move
+pub async fn think(mut search: Search, mut game: Game, limits: Limits) -> (Search, SearchResult) {
+    tokio::task::spawn_blocking(move || {
+        let result = search.search(&mut game, limits);
+        (search, result)
+    })
+    .await
+    .expect("Engine search panicked")
+}
@@ -1,9 +1,8 @@
 use crate::player::Player;
 use crate::Result;
-use axum::extract::ws::{Message, WebSocket};
-use log::{debug, info};
+use axum::extract::ws::Message;
+use chess_engine::piece::Color;
 use serde::{Deserialize, Serialize};
-use std::io::Error;
 use tokio::task::JoinHandle;
 use uuid::Uuid;
 
@@ -15,39 +14,66 @@ pub enum GameStatus {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct GameEvent {
+pub struct GameEvent {
     #[serde(rename = "type")]
-    msg_type: String,
+    pub msg_type: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    data: Option<String>,
+    pub data: Option<String>,
+}
+
+impl GameEvent {
+    pub fn new(msg_type: &str, data: Option<String>) -> Self {
+        Self {
+            msg_type: msg_type.into(),
+            data,
+        }
+    }
+
+    pub fn to_message(&self) -> Message {
+        Message::Text(serde_json::to_string(self).unwrap())
+    }
 }
 
-#[derive(Debug)]
 pub struct Game {
     draw_offered: Option<Uuid>,
     //Chat message to be sent id is the player who sent it
     chat: Option<(Uuid, String)>,
     status : GameStatus, 
+    // Moves are checked against this, the first player matched has white
+    position: chess_engine::game::Game,
+    // Move to pass on to the opponent, id is the player who made it
+    moved: Option<(Uuid, String)>,
+    // Error for the player with the id, e.g. an illegal move
+    error: Option<(Uuid, String)>,
 }
 
 pub type AxumMessageResult = Option<std::result::Result<Message, axum::Error>>;
 impl Game {
-    pub fn new() -> Self {
+    pub fn new(position: chess_engine::game::Game) -> Self {
         Self {
             draw_offered: None,
             chat: None,
             status : GameStatus::Ongoing,
+            position,
+            moved: None,
+            error: None,
         }
     }
 
-    pub fn start(mut self, mut p1: Player, mut p2: Player) -> JoinHandle<()> {
+    pub fn start(mut self, p1: Player, p2: Player) -> JoinHandle<()> {
         tokio::spawn(async move {
             let mut players = [p1 , p2];
 
+            let start = GameEvent::new("START", Some(self.position.to_fen()));
+            for p in players.iter_mut() {
+                if p.sock().send(start.to_message()).await.is_err() {
+                    return;
+                }
+            }
 
             loop {
                 if let Some((id, txt)) = &self.chat {
-                    for mut p in &players {
+                    for p in players.iter_mut() {
                         if p.id() == *id {
                             continue;
                         }
@@ -63,19 +89,59 @@ impl Game {
                     self.chat = None;
                 }
 
+                if let Some((id, mv)) = self.moved.take() {
+                    for p in players.iter_mut().filter(|p| p.id() != id) {
+                        let _ = p
+                            .sock()
+                            .send(GameEvent::new("MOVE", Some(mv.clone())).to_message())
+                            .await;
+                    }
+                }
+
+                if let Some((id, error)) = self.error.take() {
+                    for p in players.iter_mut().filter(|p| p.id() == id) {
+                        let _ = p
+                            .sock()
+                            .send(GameEvent::new("ERROR", Some(error.clone())).to_message())
+                            .await;
+                    }
+                }
+
+                if !matches!(self.status, GameStatus::Ongoing) {
+                    for p in players.iter_mut() {
+                        let data = match self.status {
+                            GameStatus::Winner(id) if id == p.id() => "WINNER",
+                            GameStatus::Winner(_) => "LOSER",
+                            _ => "DRAW",
+                        };
+                        let _ = p
+                            .sock()
+                            .send(GameEvent::new("GAME_OVER", Some(data.into())).to_message())
+                            .await;
+                    }
+                    break;
+                }
+
                 // Little fix for the tokio::select! macro
                 let fix = players.split_at_mut(1);
+                let ids = [fix.0[0].id(), fix.1[0].id()];
                 tokio::select! {
-                    val = fix.0[0].sock().recv() => self.handle_message(val , players[0].id()).await.unwrap(),
-                    val = fix.1[0].sock().recv() => self.handle_message(val , players[1].id()).await.unwrap(),
+                    val = fix.0[0].sock().recv() => self.handle_message(val , ids[0], ids).await.unwrap(),
+                    val = fix.1[0].sock().recv() => self.handle_message(val , ids[1], ids).await.unwrap(),
                 };
             }
         })
     }
 
 
-    async fn handle_message(&mut self, msg: AxumMessageResult, id: Uuid) -> Result<()> {
-        if let None = msg {
+    // ids are the white and black players
+    async fn handle_message(
+        &mut self,
+        msg: AxumMessageResult,
+        id: Uuid,
+        ids: [Uuid; 2],
+    ) -> Result<()> {
+        if msg.is_none() {
             return Ok(());
         }
 
@@ -84,7 +150,7 @@ impl Game {
                 println!("Event : {:?}", evt);
                 match evt.msg_type.as_str() {
                     "CHAT" => self.chat = Some((id, txt)),
-                    "MOVE" => {}
+                    "MOVE" => self.play(id, ids, evt.data.unwrap_or_default()),
                     "RESIGN" => {}
                     "DRAW_OFFER" => self.draw_offered = Some(id),
                     "DRAW_DECLINE" => self.draw_offered = None,
@@ -96,18 +162,51 @@ impl Game {
 
         Ok(())
     }
-}
 
+    // Check a move is legal and the sender's turn, then play it and see if the game is over
+    fn play(&mut self, id: Uuid, ids: [Uuid; 2], text: String) {
+        let game = &mut self.position;
+        let side = if id == ids[0] {
+            Color::White
+        } else {
+            Color::Black
+        };
+        // Coordinates like e2e4 or N@f3, or SAN like Nf3
+        let mv = (game.turn() == side)
+            .then(|| game.find_move(&text).or_else(|| game.parse_san(&text)))
+            .flatten();
+        let Some(mv) = mv else {
+            self.error = Some((id, "Illegal move".into()));
+            return;
+        };
 
+        self.moved = Some((id, game.coordinates(mv)));
+        game.make_move(mv);
+
+        let no_moves = game.generate_moves().is_empty();
+        let winner = if let Some(winner) = game.winner() {
+            Some(winner)
+        } else if no_moves && !game.checkers().is_empty() {
+            Some(side)
+        } else {
+            None
+        };
+        if let Some(winner) = winner {
+            self.status = GameStatus::Winner(ids[winner as usize]);
+        } else if no_moves || game.state().halfmove_clock >= 100 || game.is_threefold() {
+            self.status = GameStatus::Draw;
+        }
+    }
+}
 
-const Chat: usize = 0;
 /* JSON communication
 Chat message
 {
     "type" : "CHAT",
     "data" : "Hello World"
 }
-Move
+Move, in coordinates e.g. e2e4 or SAN e.g. Be5. It is checked and passed on to the
+opponent in coordinates, an illegal move or one out of turn gets an ERROR back
 {
     "type" : "MOVE",
     "data" : "Be5"
@@ -122,6 +221,45 @@ Draw {
 GameEnd
 {
     "type" : "GAME_OVER",
-    "data" : "WINNER/LOSER"
+    "data" : "WINNER/LOSER/DRAW"
+}
+Start, sent to both players when the game begins with the position as FEN. Join with
+/ws?variant=chess960 for a Chess960 start position, castling is then the king taking its
+rook e.g. e1h1. Players are only matched with others asking for the same variant, which
+can also be kingOfTheHill, threeCheck, atomic or crazyhouse. Crazyhouse positions have the
+pockets in brackets after the board, e.g. RNBQKBNR[Pn], and a piece is dropped with a move
+like N@f3, or P@e4 for a pawn
+{
+    "type" : "START",
+    "data" : "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1"
+}
+Error, e.g. a move that isn't legal
+{
+    "type" : "ERROR",
+    "data" : "Illegal move"
+}
+Against the engine (/ws?opponent=engine) moves are in coordinate notation e.g. e2e4
+and it can give odds by starting without a piece, e.g. /ws?opponent=engine&odds=knight.
+The odds can be pawn, knight, rook or queen
+Bughouse (/ws?variant=bughouse) starts once four players are waiting. After START each gets
+SEAT with their board and colour, partners play opposite colours on boards A and B. Every
+MOVE, and the CLOCK that follows it with both sides' milliseconds left, is sent to all four
+with the board in front. A capture puts the piece in the partner's pocket and everyone gets
+the other board as POSITION. CHAT only goes to the partner, and GAME_OVER ends both boards
+{
+    "type" : "SEAT",
+    "data" : "B black"
+}
+{
+    "type" : "MOVE",
+    "data" : "A e2e4"
+}
+{
+    "type" : "CLOCK",
+    "data" : "A 176512 180000"
+}
+{
+    "type" : "POSITION",
+    "data" : "B rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[P] b KQkq - 0 1"
 }
  */
@@ -1,65 +1,106 @@
+mod bot;
+mod bughouse;
+mod engine;
 mod game;
 mod player;
+mod setup;
+mod variant;
 
 use axum::extract::ws::{Message, WebSocket};
-use axum::extract::{ConnectInfo, State, WebSocketUpgrade};
+use axum::extract::{ConnectInfo, Query, State, WebSocketUpgrade};
+use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::routing::any;
-use axum::Router;
+use axum::routing::{any, get};
+use axum::{Json, Router};
 use axum_extra::headers::UserAgent;
 use axum_extra::TypedHeader;
+use chess_engine::piece::Piece;
+use chess_engine::search::{Limits, Search};
+use engine::EngineConfig;
 use futures::lock::Mutex;
-use futures::{SinkExt, StreamExt};
 use game::Game;
 use log::info;
 use player::Player;
-use serde_json::{json, Value};
-use std::borrow::BorrowMut;
+use serde::{Deserialize, Serialize};
+use setup::Setup;
 use std::collections::HashMap;
 use std::env;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
 use tokio::task::JoinHandle;
 use tower_http::cors::CorsLayer;
 use tower_http::trace::{DefaultMakeSpan, TraceLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use uuid::Uuid;
+use variant::Variant;
 
-const CHANNEL_BUFFER_SIZE: usize = 100;
+// Analysis requests can't tie the engine up for longer than this
+const MAX_ANALYSIS_DEPTH: u8 = 30;
+const MAX_ANALYSIS_TIME: Duration = Duration::from_secs(10);
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
 pub struct AppState {
     lobby: HashMap<Uuid, Player>,
     games: Vec<JoinHandle<()>>,
+    engine: EngineConfig,
+    // Every analysis request shares one search and takes its turn with it, so requests
+    // can't each claim a hash table and all the cores. None until the first request
+    analysis: Arc<Mutex<Option<Search>>>,
 }
 
 impl AppState {
-    pub fn join(&mut self, sock: WebSocket) {
-        let player = Player::new(sock);
+    // Skip the lobby and play the engine straight away, it may give up a piece as odds
+    pub fn join_engine(&mut self, sock: WebSocket, variant: Variant, odds: Option<Piece>) {
+        let handle = bot::start(Player::new(sock, variant), self.engine.clone(), odds);
+        self.games.push(handle);
+    }
+
+    pub fn join(&mut self, sock: WebSocket, variant: Variant) {
+        let player = Player::new(sock, variant);
         let id = player.id();
 
         self.lobby.insert(id, player);
         self.compatible(id);
     }
 
-    // Find compatible opponent for the last player that joined
+    // Find compatible opponents for the last player that joined, three of them for Bughouse
     pub fn compatible(&mut self, id: Uuid) {
-        for player in &mut self.lobby.values() {
-            if player.id() != id {
-                info!("Found match");
-                let p1 = self.lobby.remove(&player.id()).unwrap();
-                let p2 = self.lobby.remove(&id);
-
-                self.start(p1, p2.unwrap());
-                break;
-            }
+        let variant = self.lobby[&id].variant();
+        let mut ids: Vec<Uuid> = self
+            .lobby
+            .values()
+            .filter(|player| player.id() != id && player.variant() == variant)
+            .map(Player::id)
+            .take(variant.players() - 1)
+            .collect();
+        if ids.len() < variant.players() - 1 {
+            return;
         }
+
+        info!("Found match");
+        ids.push(id);
+        let players = ids
+            .iter()
+            .map(|id| self.lobby.remove(id).unwrap())
+            .collect();
+        self.start(variant, players);
     }
 
-    // Start a new game with the players from that index
-    pub fn start(&mut self, p1: Player, p2: Player) {
-        Game::new().start(p1, p2);
+    // Start a new game with the matched players, in the order they were found
+    pub fn start(&mut self, variant: Variant, players: Vec<Player>) {
+        let mut players = players.into_iter();
+        let mut next = || players.next().expect("Enough players were matched");
+
+        if variant == Variant::Bughouse {
+            let handle = bughouse::start([next(), next(), next(), next()]);
+            self.games.push(handle);
+            return;
+        }
+
+        // Both players get the same start position, for Chess960 it is picked here
+        let handle = Game::new(variant.start_position()).start(next(), next());
+        self.games.push(handle);
     }
 }
 
@@ -85,10 +126,16 @@ async fn main() {
     let state = Arc::new(Mutex::new(AppState {
         lobby: HashMap::new(),
         games: Vec::new(),
+        engine: EngineConfig::from_env(),
+        analysis: Arc::new(Mutex::new(None)),
     }));
 
     let app = Router::new()
         .route("/ws", any(ws_handler))
+        .route(
+            "/analysis",
+            get(analysis_handler).post(board_analysis_handler),
+        )
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(DefaultMakeSpan::default().include_headers(true)),
@@ -109,8 +156,20 @@ async fn main() {
     .unwrap();
 }
 
+#[derive(Deserialize)]
+struct WsParams {
+    // "engine" to play against the engine instead of waiting for another player
+    opponent: Option<String>,
+    // "standard", "chess960", "kingOfTheHill", "threeCheck", "atomic", "crazyhouse" or
+    // "bughouse", standard if left out
+    variant: Option<String>,
+    // Piece the engine gives up, "pawn", "knight", "rook" or "queen"
+    odds: Option<String>,
+}
+
 async fn ws_handler(
     State(state): State<Arc<Mutex<AppState>>>,
+    Query(params): Query<WsParams>,
     ws: WebSocketUpgrade,
     user_agent: Option<TypedHeader<UserAgent>>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
@@ -122,10 +181,117 @@ async fn ws_handler(
     };
 
     info!("{user_agent} connected at {addr}");
-    ws.on_upgrade(move |socket| handle_socket(socket, addr, state))
+    let vs_engine = params.opponent.as_deref() == Some("engine");
+    let Some(variant) = Variant::parse(params.variant.as_deref()) else {
+        return (StatusCode::BAD_REQUEST, "Unknown variant").into_response();
+    };
+    if vs_engine && variant.players() > 2 {
+        return (StatusCode::BAD_REQUEST, "The engine only plays one on one").into_response();
+    }
+    let odds = match params.odds.as_deref().map(setup::parse_odds) {
+        Some(None) => return (StatusCode::BAD_REQUEST, "Unknown odds").into_response(),
+        Some(Some(_)) if !vs_engine => {
+            return (StatusCode::BAD_REQUEST, "Only the engine gives odds").into_response()
+        }
+        odds => odds.flatten(),
+    };
+    ws.on_upgrade(move |socket| handle_socket(socket, addr, state, vs_engine, variant, odds))
+        .into_response()
+}
+
+#[derive(Deserialize)]
+struct AnalysisParams {
+    fen: String,
+    depth: Option<u8>,
+    // Milliseconds
+    movetime: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct Analysis {
+    best_move: Option<String>,
+    // Centipawns for the side to move
+    score: i32,
+    depth: u8,
+    nodes: u64,
+    pv: Vec<String>,
 }
 
-async fn handle_socket(mut sock: WebSocket, addr: SocketAddr, state: Arc<Mutex<AppState>>) {
+// GET /analysis?fen=...&depth=...&movetime=...
+async fn analysis_handler(
+    State(state): State<Arc<Mutex<AppState>>>,
+    Query(params): Query<AnalysisParams>,
+) -> std::result::Result<Json<Analysis>, (StatusCode, String)> {
+    let game = chess_engine::game::Game::from_fen(&params.fen)
+        .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
+    // The search expects a position that could really happen, e.g. with both kings
+    game.validate()
+        .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
+    Ok(analyse(&state, game, params.depth, params.movetime).await)
+}
+
+#[derive(Deserialize)]
+struct BoardAnalysisParams {
+    #[serde(flatten)]
+    setup: Setup,
+    depth: Option<u8>,
+    // Milliseconds
+    movetime: Option<u64>,
+}
+
+// POST /analysis with a board set up in the client, e.g.
+// {"pieces": {"e1": "K", "e8": "k", "d1": "Q"}, "turn": "white", "castling": ""}
+async fn board_analysis_handler(
+    State(state): State<Arc<Mutex<AppState>>>,
+    Json(params): Json<BoardAnalysisParams>,
+) -> std::result::Result<Json<Analysis>, (StatusCode, String)> {
+    let game = params
+        .setup
+        .build()
+        .map_err(|err| (StatusCode::BAD_REQUEST, err))?;
+    Ok(analyse(&state, game, params.depth, params.movetime).await)
+}
+
+async fn analyse(
+    state: &Mutex<AppState>,
+    game: chess_engine::game::Game,
+    depth: Option<u8>,
+    movetime: Option<u64>,
+) -> Json<Analysis> {
+    let (config, analysis) = {
+        let state = state.lock().await;
+        (state.engine.clone(), state.analysis.clone())
+    };
+    let mut analysis = analysis.lock().await;
+
+    let movetime = movetime
+        .map_or(MAX_ANALYSIS_TIME, Duration::from_millis)
+        .min(MAX_ANALYSIS_TIME);
+    let limits = Limits {
+        depth: depth.map(|depth| depth.min(MAX_ANALYSIS_DEPTH)),
+        movetime: Some(movetime),
+    };
+
+    let search = analysis.take().unwrap_or_else(|| config.search());
+    let (search, result) = engine::think(search, game.clone(), limits).await;
+    *analysis = Some(search);
+    Json(Analysis {
+        best_move: result.best_move.map(|mv| game.coordinates(mv)),
+        score: result.score,
+        depth: result.depth,
+        nodes: result.nodes,
+        pv: result.pv.iter().map(|&mv| game.coordinates(mv)).collect(),
+    })
+}
+
+async fn handle_socket(
+    mut sock: WebSocket,
+    addr: SocketAddr,
+    state: Arc<Mutex<AppState>>,
+    vs_engine: bool,
+    variant: Variant,
+    odds: Option<Piece>,
+) {
     if sock.send(Message::Ping(vec![1, 2, 3])).await.is_ok() {
     } else {
         println!("Could not send ping {addr}!");
@@ -133,5 +299,9 @@ async fn handle_socket(mut sock: WebSocket, addr: SocketAddr, state: Arc<Mutex<A
         // If we can not send messages, there is no way to salvage the statemachine anyway.
         return;
     }
-    state.lock().await.join(sock);
+    if vs_engine {
+        state.lock().await.join_engine(sock, variant, odds);
+    } else {
+        state.lock().await.join(sock, variant);
+    }
 }
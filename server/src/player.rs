@@ -1,18 +1,26 @@
+use crate::variant::Variant;
 use axum::extract::ws::WebSocket;
 use uuid::Uuid;
 pub struct Player {
     id: Uuid,
     sock: WebSocket,
+    // Only players wanting the same variant are matched
+    variant: Variant,
 }
 
 impl Player {
-    pub fn new(sock: WebSocket) -> Self {
+    pub fn new(sock: WebSocket, variant: Variant) -> Self {
         Player {
             id: Uuid::new_v4(),
             sock,
+            variant,
         }
     }
 
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
     pub fn id(&self) -> Uuid {
         self.id
     }
@@ -0,0 +1,91 @@
+use chess_engine::game::Game;
+use chess_engine::piece::{Color, Piece};
+use chess_engine::square::Square;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// A board set up square by square in the client, e.g. on an analysis board
+#[derive(Deserialize)]
+pub struct Setup {
+    // Square to FEN piece letter, uppercase for white e.g. {"e1": "K", "e8": "k"}
+    pieces: HashMap<String, char>,
+    // "white" or "black", white if left out
+    turn: Option<String>,
+    // Rights in FEN order e.g. "KQkq", none if left out
+    castling: Option<String>,
+}
+
+impl Setup {
+    /// The position the client set up, or why it can't be played from
+    pub fn build(&self) -> Result<Game, String> {
+        let mut game = Game::new();
+        for (sqr, &letter) in &self.pieces {
+            let sqr: Square = sqr.parse().map_err(|_| format!("Unknown square {sqr}"))?;
+            let piece = Piece::from_char(letter).ok_or(format!("Unknown piece {letter}"))?;
+            let side = if letter.is_ascii_uppercase() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            game.place_piece(side, piece, sqr);
+        }
+
+        match self.turn.as_deref() {
+            None | Some("white") => game.set_turn(Color::White),
+            Some("black") => game.set_turn(Color::Black),
+            Some(turn) => return Err(format!("Unknown side to move {turn}")),
+        }
+
+        for right in self.castling.as_deref().unwrap_or_default().chars() {
+            let (side, kingside) = match right {
+                'K' => (Color::White, true),
+                'Q' => (Color::White, false),
+                'k' => (Color::Black, true),
+                'q' => (Color::Black, false),
+                _ => return Err(format!("Unknown castling right {right}")),
+            };
+            game.set_castling(side, kingside, true);
+        }
+
+        // Castling rights are for normal chess, not Chess960, on a board set up by hand
+        game.set_chess960(false);
+        game.validate().map_err(|err| err.to_string())?;
+        Ok(game)
+    }
+}
+
+/// Piece given up in an odds game, "pawn", "knight", "rook" or "queen"
+pub fn parse_odds(name: &str) -> Option<Piece> {
+    match name {
+        "pawn" => Some(Piece::Pawn),
+        "knight" => Some(Piece::Knight),
+        "rook" => Some(Piece::Rook),
+        "queen" => Some(Piece::Queen),
+        _ => None,
+    }
+}
+
+/// Take a piece off the start position for a side giving odds. It is the f-pawn for pawn
+/// odds, otherwise the piece furthest towards the queenside
+pub fn give_odds(game: &mut Game, side: Color, piece: Piece) {
+    let sqr = if piece == Piece::Pawn {
+        let rank = (side.back_rank() as i8 + side.forward()) as u8;
+        Square::new(5, rank)
+    } else {
+        game.piece_bb(side, piece)
+            .into_iter()
+            .min_by_key(|sqr| sqr.file())
+    };
+    let Some(sqr) = sqr else {
+        return;
+    };
+    game.clear_square(sqr);
+
+    // Without its rook the king can't castle that way any more
+    for kingside in [true, false] {
+        let rook = Square::new(game.castling_file(side, kingside), side.back_rank()).unwrap();
+        if game.piece_at(rook) != Some((side, Piece::Rook)) {
+            game.set_castling(side, kingside, false);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use chess_engine::game::{Game, CHESS960_POSITIONS, STANDARD_POSITION};
+use chess_engine::variant::{
+    self as rules, ATOMIC, BUGHOUSE, CRAZYHOUSE, KING_OF_THE_HILL, STANDARD, THREE_CHECK,
+};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// Rules a game is played under, chosen when joining with /ws?variant=...
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Variant {
+    Standard,
+    // Fischer Random, the back rank is shuffled but the same for both players
+    Chess960,
+    KingOfTheHill,
+    ThreeCheck,
+    Atomic,
+    // Captured pieces can be dropped back on the board, sent as e.g. N@f3
+    Crazyhouse,
+    // Two teams of two on linked boards, see bughouse.rs
+    Bughouse,
+}
+
+impl Variant {
+    // Standard when nothing is asked for, None for a variant we don't play
+    pub fn parse(name: Option<&str>) -> Option<Self> {
+        match name {
+            None | Some("standard") => Some(Variant::Standard),
+            Some("chess960") => Some(Variant::Chess960),
+            Some("kingOfTheHill") => Some(Variant::KingOfTheHill),
+            Some("threeCheck") => Some(Variant::ThreeCheck),
+            Some("atomic") => Some(Variant::Atomic),
+            Some("crazyhouse") => Some(Variant::Crazyhouse),
+            Some("bughouse") => Some(Variant::Bughouse),
+            _ => None,
+        }
+    }
+
+    // The engine's rules for the variant, Chess960 only changes the start position
+    fn rules(self) -> &'static dyn rules::Variant {
+        match self {
+            Variant::Standard | Variant::Chess960 => &STANDARD,
+            Variant::KingOfTheHill => &KING_OF_THE_HILL,
+            Variant::ThreeCheck => &THREE_CHECK,
+            Variant::Atomic => &ATOMIC,
+            Variant::Crazyhouse => &CRAZYHOUSE,
+            Variant::Bughouse => &BUGHOUSE,
+        }
+    }
+
+    /// How many players a game needs before it can start
+    pub fn players(self) -> usize {
+        match self {
+            Variant::Bughouse => 4,
+            _ => 2,
+        }
+    }
+
+    /// A fresh start position, for Chess960 one of the 960 picked at random
+    pub fn start_position(self) -> Game {
+        let number = match self {
+            Variant::Chess960 => {
+                let random = RandomState::new().build_hasher().finish();
+                (random % CHESS960_POSITIONS as u64) as u16
+            }
+            _ => STANDARD_POSITION,
+        };
+
+        let mut game = Game::new();
+        game.init(number);
+        // Castling is sent as the king taking its rook even if the standard setup comes up
+        game.set_chess960(self == Variant::Chess960);
+        game.set_variant(self.rules());
+        game
+    }
+}